@@ -0,0 +1,64 @@
+// Insert, query and update throughput of every broad phase backend on a wide level.
+// Run with `cargo bench --bench broad_phase`.
+//
+// The game is a binary crate, so the modules the backends live in are compiled in here directly.
+#![allow(dead_code, unused_imports)]
+
+use std::time::Instant;
+
+use ggez::graphics::Rect;
+
+#[path = "../src/consts.rs"]
+mod consts;
+#[path = "../src/collisions/mod.rs"]
+mod collisions;
+#[path = "../src/quadtree/mod.rs"]
+mod quadtree;
+#[path = "../src/utils"]
+mod utils {
+    pub mod test_rng;
+}
+
+use collisions::{BroadPhase, BroadPhaseKind, EntryId};
+use utils::test_rng::Rng;
+
+
+fn main() {
+    let level = Rect::new(0., 0., 50000., 1000.);
+    for count in [1000, 5000, 20000] {
+        for kind in BroadPhaseKind::ALL {
+            let mut rng = Rng(7);
+            let mut index: Box<dyn BroadPhase<()>> = kind.build(level);
+
+            let start = Instant::now();
+            let ids: Vec<EntryId> = (0..count).map(|_| index.insert(rng.rect(&level, 64.), ())).collect();
+            let insert = start.elapsed();
+
+            let start = Instant::now();
+            let mut found = 0;
+            for _ in 0..count {
+                found += index.query(&rng.rect(&level, 300.)).len();
+            }
+            let query = start.elapsed();
+
+            let start = Instant::now();
+            for id in &ids {
+                let mut bounds = index.get(*id).unwrap().bounds;
+                bounds.x += rng.range(-8., 8.);
+                bounds.y += rng.range(-8., 8.);
+                index.update(*id, bounds);
+            }
+            let update = start.elapsed();
+
+            println!(
+                "{:>6} objects {:<14} insert {:>8.0} ns/op  query {:>8.0} ns/op  update {:>8.0} ns/op  ({} found)",
+                count,
+                format!("{:?}", kind),
+                insert.as_nanos() as f64 / count as f64,
+                query.as_nanos() as f64 / count as f64,
+                update.as_nanos() as f64 / count as f64,
+                found
+            );
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use serde::Deserialize;
+
+
+/// Named moment in an animation, fired when playback reaches the frame it is attached to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(from = "String")]
+pub enum AnimationEvent {
+    // Attack frames that can hurt, from start to end
+    HitStart,
+    HitEnd,
+    Footstep,
+    SpawnProjectile,
+    Custom(String)
+}
+
+impl From<String> for AnimationEvent {
+    fn from(name: String) -> Self {
+        AnimationEvent::from_name(&name)
+    }
+}
+
+impl AnimationEvent {
+    pub fn from_name(name: &str) -> Self {
+        match name.trim() {
+            "hit_start" => AnimationEvent::HitStart,
+            "hit_end" => AnimationEvent::HitEnd,
+            "footstep" => AnimationEvent::Footstep,
+            "spawn_projectile" => AnimationEvent::SpawnProjectile,
+            other => AnimationEvent::Custom(other.to_string())
+        }
+    }
+}
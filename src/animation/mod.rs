@@ -1,106 +1,129 @@
+use std::io::Read;
 use std::path::Path;
 
-use ggez::{Context, GameResult};
+use ggez::{Context, GameError, GameResult};
+use ggez::filesystem;
 use ggez::graphics::{self, *};
 
-use glam::Vec2;
+
+pub mod event;
+pub use event::AnimationEvent;
+
+pub mod sheet;
+pub use sheet::{Frame, SpriteSheet};
+
+pub mod timer;
+pub use timer::{FrameTimer, LoopMode};
 
 
 pub struct SpriteAnimation {
     pub image: Image,
-    pub performing: bool,
-    pub src_x: f32,
-    pub image_idx: i8,
-    pub image_count: i8,
-    pub image_width: f32
+    pub timer: FrameTimer,
+    frames: Vec<Frame>,
+    // Fired since the last `take_events`
+    events: Vec<AnimationEvent>
 }
 
 
 impl SpriteAnimation {
 
+    /// Uses the metadata next to the image if there is any, otherwise
+    /// the image is taken as a single row of square frames.
     pub fn new(ctx: &mut Context, image_path: &str) -> Self {
         let image = graphics::Image::new(ctx, Path::new(image_path)).unwrap();
-        let image_count = (image.width() / image.height()) as i8;
+        let sheet = match load_sidecar(ctx, image_path) {
+            Some(Ok(sheet)) => Some(sheet),
+            Some(Err(e)) => {
+                eprintln!("Could not read the frames of {}, using square frames: {}", image_path, e);
+                None
+            },
+            None => None
+        };
+        let sheet = sheet.unwrap_or_else(|| {
+            let (w, h) = (image.width() as f32, image.height() as f32);
+            SpriteSheet::strip(image_path, w, h, (w / h) as usize)
+        });
+
+        Self::with_frames(image, sheet.frames, LoopMode::default())
+    }
+
+    /// Animation playing the frames of `tag`, or all of them, of a sheet with several animations.
+    /// Fails when the sheet has no such tag.
+    pub fn from_sheet(ctx: &mut Context, sheet: &SpriteSheet, tag: Option<&str>) -> GameResult<Self> {
+        let tag = match tag {
+            Some(name) => Some(sheet.tag(name)
+                .ok_or_else(|| GameError::ResourceLoadError(format!("{} has no tag {}", sheet.image, name)))?),
+            None => None
+        };
+        let image = graphics::Image::new(ctx, Path::new(&sheet.image))?;
+        let mode = tag.map_or(LoopMode::default(), |tag| tag.mode);
+        Ok(Self::with_frames(image, sheet.frames_for(tag), mode))
+    }
+
+    fn with_frames(image: Image, frames: Vec<Frame>, mode: LoopMode) -> Self {
+        let mut timer = FrameTimer::with_durations(frames.iter().map(|frame| frame.duration).collect());
+        timer.mode = mode;
+
         Self {
             image: image,
-            image_idx: 0,
-            src_x: 0.,
-            image_count: image_count,
-            image_width: 1. / image_count as f32,
-            performing: false
+            timer: timer,
+            frames: frames,
+            events: Vec::new()
         }
     }
 
-    pub fn next_x(&mut self) -> f32 {
-        if self.image_idx == self.image_count {
-            self.image_idx = 0;
-        }
-
-        let x = 1. - ((self.image_count as f32 - self.image_idx as f32) / self.image_count as f32);
+    /// Moves the animation on by `dt` seconds, returns `true` when the frame changed.
+    /// Events of every frame reached on the way are queued, even frames skipped over.
+    pub fn update(&mut self, dt: f32) -> bool {
+        let changed = self.timer.update(dt);
+        self.queue_events();
+        changed
+    }
 
-        self.image_idx += 1;
+    /// Shows frame `idx` without waiting for the timer, queueing its events if it wasn't showing.
+    pub fn set_frame(&mut self, idx: usize) {
+        self.timer.set_frame(idx);
+        self.queue_events();
+    }
 
-        return x;
+    fn queue_events(&mut self) {
+        for idx in self.timer.take_entered() {
+            self.events.extend(self.frames[idx].events.iter().cloned());
+        }
     }
-}
 
+    /// Events fired since the last call.
+    pub fn take_events(&mut self) -> Vec<AnimationEvent> {
+        std::mem::take(&mut self.events)
+    }
 
-pub struct MovingBackground {
-    image: graphics::Image,
-    step: f32,
-    step_size: f32
-}
+    pub fn restart(&mut self) {
+        self.timer.restart();
+    }
 
-impl MovingBackground {
+    pub fn frame(&self) -> &Frame {
+        &self.frames[self.timer.frame()]
+    }
 
-    pub fn new(_ctx: &mut Context, image_path: &str, step_size: f32) -> Self {
-        Self {
-            image: graphics::Image::new(_ctx, Path::new(image_path)).unwrap(),
-            step: 0.,
-            step_size: step_size
-        }
+    /// Part of the image the current frame covers, relative to the image size.
+    pub fn src(&self) -> Rect {
+        let rect = self.frame().rect;
+        let (w, h) = (self.image.width() as f32, self.image.height() as f32);
+        Rect::new(rect.x / w, rect.y / h, rect.w / w, rect.h / h)
     }
+}
 
-    pub fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
-        if self.step as i8 > 100 {
-            self.step = 0.;
-        }
 
-        self.step += self.step_size;
+/// Frame metadata stored next to `image_path`, `None` when there is none.
+pub fn load_sidecar(_ctx: &mut Context, image_path: &str) -> Option<GameResult<SpriteSheet>> {
+    let path = SpriteSheet::sidecars(image_path).into_iter().find(|path| filesystem::exists(_ctx, path))?;
+    Some(load_sheet(_ctx, &path))
+}
 
-        Ok(())
-    }
 
-    pub fn draw(&mut self, _ctx: &mut Context)  {
-
-        let step = self.step as f32 / 100.;
-
-        let (w, h) = graphics::size(_ctx);
-        let mut scale_x  = (w * (1. - step as f32)) / (self.image.width() as f32 * (1. - step as f32));
-        let mut scale_y  = h / self.image.height() as f32;
-        let params = graphics::DrawParam::default()
-            .scale(Vec2::new(scale_x, scale_y))
-            .src(graphics::Rect {
-                x: step as f32,
-                y: 0f32,
-                w: 1. - step as f32,
-                h: 1f32
-            })
-            .dest(Vec2::new(0., 0.));
-        self.image.draw(_ctx, params).unwrap();
-
-        
-        scale_x  = (w * step) / (self.image.width() as f32 * step);
-        scale_y  = h / self.image.height() as f32;
-        let params = graphics::DrawParam::default()
-            .scale(Vec2::new(scale_x, scale_y))
-            .src(graphics::Rect {
-                x: 0.,
-                y: 0f32,
-                w: step as f32,
-                h: 1f32
-            })
-            .dest(Vec2::new(self.image.width() as f32 * scale_x * (1. - step as f32), 0.));
-        self.image.draw(_ctx, params).unwrap();
-    }
-}
\ No newline at end of file
+pub fn load_sheet(_ctx: &mut Context, path: &str) -> GameResult<SpriteSheet> {
+    let mut file = filesystem::open(_ctx, path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    SpriteSheet::parse(path, &contents)
+}
@@ -0,0 +1,566 @@
+use std::collections::BTreeMap;
+
+use ggez::{GameError, GameResult};
+use ggez::graphics::Rect;
+
+use glam::Vec2;
+use serde::Deserialize;
+
+use crate::animation::{AnimationEvent, LoopMode};
+use crate::consts::ANIMATION_FRAME_TIME;
+use crate::utils::resolve_path;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    // Pixels of the image the frame covers
+    pub rect: Rect,
+    // Where `rect` sits in the untrimmed frame
+    pub offset: Vec2,
+    // Size of the untrimmed frame
+    pub size: Vec2,
+    // Point of the untrimmed frame that stays in place from frame to frame, bottom center by default
+    pub pivot: Vec2,
+    // Seconds
+    pub duration: f32,
+    // Fired when playback reaches the frame
+    pub events: Vec<AnimationEvent>
+}
+
+impl Frame {
+    pub fn new(rect: Rect, duration: f32) -> Self {
+        Self {
+            rect: rect,
+            offset: Vec2::ZERO,
+            size: Vec2::new(rect.w, rect.h),
+            pivot: Vec2::new(rect.w / 2., rect.h),
+            duration: duration,
+            events: Vec::new()
+        }
+    }
+}
+
+
+/// Named range of frames, e.g. one animation out of a sheet holding several.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    // First and last frame, inclusive
+    pub from: usize,
+    pub to: usize,
+    pub mode: LoopMode,
+    // Plays from `to` down to `from`
+    pub reverse: bool
+}
+
+
+/// Where each frame of an image is, read from metadata next to the image: an Aseprite or
+/// TexturePacker JSON export (`<image>.json`), or a descriptor (`<image>.toml`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteSheet {
+    pub image: String,
+    pub frames: Vec<Frame>,
+    pub tags: Vec<Tag>
+}
+
+impl SpriteSheet {
+    /// Single row of `count` frames filling an image of `width` by `height` pixels.
+    pub fn strip(image: &str, width: f32, height: f32, count: usize) -> Self {
+        let count = count.max(1);
+        let w = width / count as f32;
+        Self {
+            image: image.to_string(),
+            frames: (0..count).map(|idx| Frame::new(Rect::new(idx as f32 * w, 0., w, height), ANIMATION_FRAME_TIME)).collect(),
+            tags: Vec::new()
+        }
+    }
+
+    /// Metadata files an image can come with, in the order they are looked for.
+    pub fn sidecars(image: &str) -> [String; 2] {
+        let stem = image.strip_suffix(".png").unwrap_or(image);
+        [format!("{}.json", stem), format!("{}.toml", stem)]
+    }
+
+    /// Reads metadata from `contents`, in JSON or TOML depending on the extension of `path`.
+    /// The image path in the metadata is relative to the metadata file.
+    pub fn parse(path: &str, contents: &str) -> GameResult<Self> {
+        let dir = path.rfind('/').map_or("", |idx| &path[..idx]);
+        let sheet = if path.ends_with(".toml") {
+            Self::from_descriptor(contents, dir)
+        } else {
+            Self::from_json(contents, dir)
+        };
+
+        sheet.map_err(|e| GameError::ResourceLoadError(format!("{}: {}", path, e)))
+    }
+
+    /// Aseprite or TexturePacker JSON export, with frames as an array or a hash.
+    pub fn from_json(contents: &str, dir: &str) -> Result<Self, String> {
+        let raw: RawJsonSheet = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+
+        let raw_frames = match raw.frames {
+            RawJsonFrames::List(frames) => frames,
+            // Hash keys come back sorted, put "run 10" after "run 9"
+            RawJsonFrames::Map(frames) => {
+                let mut frames: Vec<(String, RawJsonFrame)> = frames.into_iter().collect();
+                frames.sort_by_key(|(name, _)| natural_key(name));
+                frames.into_iter().map(|(_, frame)| frame).collect()
+            }
+        };
+
+        let mut frames = Vec::new();
+        for raw_frame in raw_frames {
+            if raw_frame.rotated {
+                return Err(format!("frame {} is rotated, export without rotation", raw_frame.filename));
+            }
+
+            let rect = raw_frame.frame.rect();
+            let mut frame = Frame::new(rect, raw_frame.duration.map_or(ANIMATION_FRAME_TIME, |ms| ms / 1000.));
+            if let Some(trim) = raw_frame.sprite_source_size {
+                frame.offset = Vec2::new(trim.x, trim.y);
+            }
+            if let Some(size) = raw_frame.source_size {
+                frame.size = Vec2::new(size.w, size.h);
+            }
+            frame.pivot = match raw_frame.pivot {
+                // TexturePacker pivots are relative to the frame size
+                Some(pivot) => Vec2::new(pivot.x * frame.size.x, pivot.y * frame.size.y),
+                None => Vec2::new(frame.size.x / 2., frame.size.y)
+            };
+            frames.push(frame);
+        }
+
+        // Aseprite keeps pivots in slices, a key applies from its frame on
+        for slice in &raw.meta.slices {
+            let mut keys: Vec<&RawSliceKey> = slice.keys.iter().filter(|key| key.pivot.is_some()).collect();
+            keys.sort_by_key(|key| key.frame);
+            for (idx, key) in keys.iter().enumerate() {
+                let end = keys.get(idx + 1).map_or(frames.len(), |next| next.frame);
+                let pivot = key.pivot.as_ref().unwrap();
+                for frame in frames.iter_mut().take(end).skip(key.frame) {
+                    frame.pivot = Vec2::new(key.bounds.x + pivot.x, key.bounds.y + pivot.y);
+                }
+            }
+        }
+
+        // Events are the user data of a tag, comma separated, fired on the tag's first frame
+        for tag in &raw.meta.frame_tags {
+            if let (Some(data), Some(frame)) = (&tag.data, frames.get_mut(tag.from)) {
+                frame.events.extend(data.split(',').filter(|name| !name.trim().is_empty()).map(AnimationEvent::from_name));
+            }
+        }
+
+        let tags = raw.meta.frame_tags.into_iter().map(|tag| Tag {
+            mode: if tag.direction == "pingpong" { LoopMode::PingPong } else { LoopMode::Loop },
+            reverse: tag.direction == "reverse",
+            name: tag.name,
+            from: tag.from,
+            to: tag.to
+        }).collect();
+
+        Self::checked(resolve_path(dir, &raw.meta.image), frames, tags)
+    }
+
+    /// Hand written descriptor: either a grid of equal frames or a list of frame rects.
+    pub fn from_descriptor(contents: &str, dir: &str) -> Result<Self, String> {
+        let raw: RawDescriptor = toml::from_str(contents).map_err(|e| e.to_string())?;
+        let default_pivot = raw.pivot.map(|[x, y]| Vec2::new(x, y));
+
+        let mut frames: Vec<Frame> = raw.frames.iter().map(|raw_frame| {
+            let mut frame = Frame::new(Rect::new(raw_frame.x, raw_frame.y, raw_frame.w, raw_frame.h), raw_frame.duration.unwrap_or(raw.duration));
+            if let Some(pivot) = raw_frame.pivot.map(|[x, y]| Vec2::new(x, y)).or(default_pivot) {
+                frame.pivot = pivot;
+            }
+            frame
+        }).collect();
+
+        if frames.is_empty() {
+            let (w, h) = match (raw.frame_width, raw.frame_height) {
+                (Some(w), Some(h)) => (w, h),
+                _ => return Err("needs either frames or frame_width and frame_height".to_string())
+            };
+            let columns = raw.columns.unwrap_or(raw.count).max(1);
+
+            for idx in 0..raw.count {
+                let (column, row) = ((idx % columns) as f32, (idx / columns) as f32);
+                let mut frame = Frame::new(Rect::new(
+                    raw.margin + column * (w + raw.spacing),
+                    raw.margin + row * (h + raw.spacing),
+                    w,
+                    h
+                ), raw.duration);
+                if let Some(pivot) = default_pivot {
+                    frame.pivot = pivot;
+                }
+                frames.push(frame);
+            }
+        }
+
+        if let Some(durations) = raw.durations {
+            for (frame, duration) in frames.iter_mut().zip(durations) {
+                frame.duration = duration;
+            }
+        }
+
+        for event in raw.events {
+            match frames.get_mut(event.frame) {
+                Some(frame) => frame.events.push(event.name),
+                None => return Err(format!("event {:?} is on frame {} of {}", event.name, event.frame, frames.len()))
+            }
+        }
+
+        let tags = raw.tags.into_iter().map(|tag| Tag {
+            name: tag.name,
+            from: tag.from,
+            to: tag.to,
+            mode: tag.mode,
+            reverse: tag.reverse
+        }).collect();
+
+        Self::checked(resolve_path(dir, &raw.image), frames, tags)
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&Tag> {
+        self.tags.iter().find(|tag| tag.name == name)
+    }
+
+    /// Frames `tag` plays in order, every frame when `None`.
+    pub fn frames_for(&self, tag: Option<&Tag>) -> Vec<Frame> {
+        match tag {
+            Some(tag) => {
+                let mut frames = self.frames[tag.from..=tag.to].to_vec();
+                if tag.reverse {
+                    frames.reverse();
+                }
+                frames
+            },
+            None => self.frames.clone()
+        }
+    }
+
+    fn checked(image: String, frames: Vec<Frame>, tags: Vec<Tag>) -> Result<Self, String> {
+        if frames.is_empty() {
+            return Err("no frames".to_string());
+        }
+        if let Some(tag) = tags.iter().find(|tag| tag.from > tag.to || tag.to >= frames.len()) {
+            return Err(format!("tag {} covers frames {} to {} of {}", tag.name, tag.from, tag.to, frames.len()));
+        }
+
+        Ok(Self {
+            image: image,
+            frames: frames,
+            tags: tags
+        })
+    }
+}
+
+
+// Sorts names by their text, then by the value of the number in them
+fn natural_key(name: &str) -> (String, u64) {
+    let digits: String = name.chars().rev()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<Vec<char>>().into_iter().rev().collect();
+    let text: String = name.chars().filter(|c| !c.is_ascii_digit()).collect();
+    (text, digits.parse().unwrap_or(0))
+}
+
+
+#[derive(Deserialize)]
+struct RawJsonSheet {
+    frames: RawJsonFrames,
+    meta: RawMeta
+}
+
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawJsonFrames {
+    List(Vec<RawJsonFrame>),
+    Map(BTreeMap<String, RawJsonFrame>)
+}
+
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawJsonFrame {
+    #[serde(default)]
+    filename: String,
+    frame: RawRect,
+    #[serde(default)]
+    rotated: bool,
+    sprite_source_size: Option<RawRect>,
+    source_size: Option<RawSize>,
+    // Milliseconds
+    duration: Option<f32>,
+    pivot: Option<RawPoint>
+}
+
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawMeta {
+    image: String,
+    #[serde(default)]
+    frame_tags: Vec<RawJsonTag>,
+    #[serde(default)]
+    slices: Vec<RawSlice>
+}
+
+
+#[derive(Deserialize)]
+struct RawJsonTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+    data: Option<String>
+}
+
+
+#[derive(Deserialize)]
+struct RawSlice {
+    #[serde(default)]
+    keys: Vec<RawSliceKey>
+}
+
+
+#[derive(Deserialize)]
+struct RawSliceKey {
+    frame: usize,
+    bounds: RawRect,
+    pivot: Option<RawPoint>
+}
+
+
+#[derive(Deserialize)]
+struct RawRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32
+}
+
+impl RawRect {
+    fn rect(&self) -> Rect {
+        Rect::new(self.x, self.y, self.w, self.h)
+    }
+}
+
+
+#[derive(Deserialize)]
+struct RawSize {
+    w: f32,
+    h: f32
+}
+
+
+#[derive(Deserialize)]
+struct RawPoint {
+    x: f32,
+    y: f32
+}
+
+
+#[derive(Deserialize)]
+struct RawDescriptor {
+    image: String,
+    // Grid of equal frames, read left to right and top to bottom
+    frame_width: Option<f32>,
+    frame_height: Option<f32>,
+    #[serde(default)]
+    count: usize,
+    // Frames per row, all of them by default
+    columns: Option<usize>,
+    #[serde(default)]
+    margin: f32,
+    #[serde(default)]
+    spacing: f32,
+    // Or each frame on its own
+    #[serde(default)]
+    frames: Vec<RawDescriptorFrame>,
+    #[serde(default = "default_duration")]
+    duration: f32,
+    // Per frame durations, overriding `duration`
+    durations: Option<Vec<f32>>,
+    pivot: Option<[f32; 2]>,
+    #[serde(default)]
+    tags: Vec<RawDescriptorTag>,
+    #[serde(default)]
+    events: Vec<RawDescriptorEvent>
+}
+
+
+#[derive(Deserialize)]
+struct RawDescriptorFrame {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    duration: Option<f32>,
+    pivot: Option<[f32; 2]>
+}
+
+
+#[derive(Deserialize)]
+struct RawDescriptorTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    mode: LoopMode,
+    #[serde(default)]
+    reverse: bool
+}
+
+#[derive(Deserialize)]
+struct RawDescriptorEvent {
+    frame: usize,
+    name: AnimationEvent
+}
+
+fn default_duration() -> f32 {
+    ANIMATION_FRAME_TIME
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASEPRITE: &str = r#"{
+        "frames": {
+            "punk 10.aseprite": {"frame": {"x": 0, "y": 48, "w": 20, "h": 30}, "rotated": false, "trimmed": true,
+                "spriteSourceSize": {"x": 14, "y": 18, "w": 20, "h": 30}, "sourceSize": {"w": 48, "h": 48}, "duration": 150},
+            "punk 2.aseprite": {"frame": {"x": 48, "y": 0, "w": 48, "h": 48}, "duration": 100},
+            "punk 1.aseprite": {"frame": {"x": 0, "y": 0, "w": 48, "h": 48}, "duration": 100}
+        },
+        "meta": {
+            "image": "punk.png",
+            "size": {"w": 96, "h": 96},
+            "frameTags": [
+                {"name": "idle", "from": 0, "to": 1, "direction": "pingpong"},
+                {"name": "hit", "from": 1, "to": 2, "direction": "reverse", "data": "hit_start, shout"}
+            ],
+            "slices": [{"name": "feet", "keys": [
+                {"frame": 1, "bounds": {"x": 10, "y": 40, "w": 20, "h": 8}, "pivot": {"x": 10, "y": 8}}
+            ]}]
+        }
+    }"#;
+
+    #[test]
+    fn reads_aseprite_exports() {
+        let sheet = SpriteSheet::from_json(ASEPRITE, "/chars/punk").unwrap();
+        assert_eq!(sheet.image, "/chars/punk/punk.png");
+
+        // Hash order fixed up, "punk 10" comes last
+        let rects: Vec<Rect> = sheet.frames.iter().map(|frame| frame.rect).collect();
+        assert_eq!(rects[0], Rect::new(0., 0., 48., 48.));
+        assert_eq!(rects[2], Rect::new(0., 48., 20., 30.));
+
+        let trimmed = &sheet.frames[2];
+        assert_eq!((trimmed.offset, trimmed.size, trimmed.duration), (Vec2::new(14., 18.), Vec2::new(48., 48.), 0.15));
+
+        assert_eq!(sheet.frames[0].pivot, Vec2::new(24., 48.));
+        assert_eq!(sheet.frames[1].pivot, Vec2::new(20., 48.));
+        assert_eq!(sheet.frames[2].pivot, Vec2::new(20., 48.));
+
+        assert_eq!(sheet.tag("idle").unwrap().mode, LoopMode::PingPong);
+        let hit = sheet.frames_for(sheet.tag("hit"));
+        assert_eq!(hit.iter().map(|frame| frame.rect.y).collect::<Vec<f32>>(), vec![48., 0.]);
+        assert_eq!(sheet.frames[1].events, vec![AnimationEvent::HitStart, AnimationEvent::Custom("shout".to_string())]);
+        assert!(sheet.frames[0].events.is_empty());
+    }
+
+    #[test]
+    fn reads_texture_packer_arrays() {
+        let json = r#"{
+            "frames": [
+                {"filename": "a", "frame": {"x": 0, "y": 0, "w": 30, "h": 20}, "pivot": {"x": 0.5, "y": 0.25}},
+                {"filename": "b", "frame": {"x": 30, "y": 0, "w": 30, "h": 20}, "rotated": true}
+            ],
+            "meta": {"image": "atlas.png"}
+        }"#;
+        assert!(SpriteSheet::from_json(json, "").is_err());
+
+        let json = json.replace("\"rotated\": true", "\"rotated\": false");
+        let sheet = SpriteSheet::from_json(&json, "/objects").unwrap();
+        assert_eq!(sheet.frames[0].pivot, Vec2::new(15., 5.));
+        assert_eq!(sheet.frames[1].duration, ANIMATION_FRAME_TIME);
+    }
+
+    #[test]
+    fn reads_descriptors() {
+        let grid = r#"
+            image = "sheet.png"
+            frame_width = 30
+            frame_height = 20
+            count = 5
+            columns = 2
+            spacing = 2
+            durations = [0.2, 0.1]
+
+            events = [
+                { frame = 1, name = "footstep" },
+                { frame = 3, name = "spawn_projectile" }
+            ]
+
+            [[tags]]
+            name = "open"
+            from = 1
+            to = 4
+            mode = "hold"
+        "#;
+        let sheet = SpriteSheet::from_descriptor(grid, "/objects").unwrap();
+        assert_eq!(sheet.frames.len(), 5);
+        assert_eq!(sheet.frames[3].rect, Rect::new(32., 22., 30., 20.));
+        assert_eq!(sheet.frames.iter().map(|frame| frame.duration).collect::<Vec<f32>>(), vec![0.2, 0.1, 0.1, 0.1, 0.1]);
+        assert_eq!(sheet.tag("open").unwrap().mode, LoopMode::Hold);
+        assert_eq!(sheet.frames_for(sheet.tag("open")).len(), 4);
+        assert_eq!(sheet.frames[1].events, vec![AnimationEvent::Footstep]);
+        assert_eq!(sheet.frames[3].events, vec![AnimationEvent::SpawnProjectile]);
+        assert!(SpriteSheet::from_descriptor(&grid.replace("frame = 3", "frame = 5"), "").is_err());
+
+        let listed = r#"
+            image = "../atlas.png"
+            pivot = [8, 16]
+            frames = [
+                { x = 0, y = 0, w = 16, h = 16 },
+                { x = 16, y = 0, w = 24, h = 16, duration = 0.3, pivot = [12, 16] }
+            ]
+            tags = [{ name = "all", from = 0, to = 2 }]
+        "#;
+        assert!(SpriteSheet::from_descriptor(listed, "/objects").is_err());
+
+        let sheet = SpriteSheet::from_descriptor(&listed.replace("to = 2", "to = 1"), "/objects").unwrap();
+        assert_eq!(sheet.image, "/atlas.png");
+        assert_eq!(sheet.frames[0].pivot, Vec2::new(8., 16.));
+        assert_eq!((sheet.frames[1].pivot, sheet.frames[1].duration), (Vec2::new(12., 16.), 0.3));
+    }
+
+    #[test]
+    fn shipped_metadata_matches_its_image() {
+        let resources = concat!(env!("CARGO_MANIFEST_DIR"), "/resources");
+        let mut pending = vec![std::path::PathBuf::from(resources)];
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+
+                // Only metadata sitting next to an image of the same name
+                let name = path.to_str().unwrap();
+                let image = format!("{}.png", name.rsplit_once('.').unwrap().0);
+                if !std::path::Path::new(&image).exists() || name.ends_with(".png") {
+                    continue;
+                }
+
+                let sheet = SpriteSheet::parse(&name[resources.len()..], &std::fs::read_to_string(&path).unwrap()).unwrap();
+                assert_eq!(format!("{}{}", resources, sheet.image), image);
+            }
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use serde::Deserialize;
+
+
+// Shortest frame, keeps a zero duration from stalling `update`
+const MIN_FRAME_TIME: f32 = 0.001;
+
+
+/// What happens once the last frame has been shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoopMode {
+    // Starts over from the first frame
+    Loop,
+    // Finishes and goes back to the first frame
+    Once,
+    // Plays backwards to the first frame, then forwards again
+    PingPong,
+    // Finishes and stays on the last frame
+    Hold
+}
+
+impl Default for LoopMode {
+    fn default() -> Self {
+        LoopMode::Loop
+    }
+}
+
+
+/// Decides which frame of an animation shows, from the time that passed.
+#[derive(Debug, Clone)]
+pub struct FrameTimer {
+    // Seconds each frame stays on screen
+    durations: Vec<f32>,
+    pub mode: LoopMode,
+    // Playback rate, 2 plays twice as fast
+    pub speed: f32,
+    frame: usize,
+    // 1 forwards, -1 backwards while ping-ponging
+    direction: i8,
+    elapsed: f32,
+    finished: bool,
+    // Frames shown since the last `take_entered`, in order
+    entered: Vec<usize>
+}
+
+impl FrameTimer {
+    pub fn with_durations(durations: Vec<f32>) -> Self {
+        let mut timer = Self {
+            durations: Vec::new(),
+            mode: LoopMode::default(),
+            speed: 1.,
+            frame: 0,
+            direction: 1,
+            elapsed: 0.,
+            finished: false,
+            entered: vec![0]
+        };
+        timer.set_durations(durations);
+        timer
+    }
+
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.durations.len()
+    }
+
+    /// Gives every frame the same duration.
+    pub fn set_frame_time(&mut self, frame_time: f32) {
+        let count = self.frame_count();
+        self.set_durations(vec![frame_time; count]);
+    }
+
+    /// One duration per frame, the frame count follows.
+    pub fn set_durations(&mut self, durations: Vec<f32>) {
+        self.durations = durations.into_iter().map(|duration| duration.max(MIN_FRAME_TIME)).collect();
+        if self.durations.is_empty() {
+            self.durations.push(MIN_FRAME_TIME);
+        }
+        self.frame = self.frame.min(self.durations.len() - 1);
+    }
+
+    /// A `Once` or `Hold` animation that has played through. Looping ones never finish.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Share of the animation played so far, 1 once a `Once` or `Hold` animation has finished.
+    pub fn progress(&self) -> f32 {
+        if self.finished {
+            return 1.;
+        }
+
+        let total: f32 = self.durations.iter().sum();
+        let played = self.durations[..self.frame].iter().sum::<f32>() + self.elapsed;
+        (played / total).min(1.)
+    }
+
+    /// Back to the first frame, ready to play again.
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.direction = 1;
+        self.elapsed = 0.;
+        self.finished = false;
+        self.entered.push(0);
+    }
+
+    /// Shows frame `idx` without waiting for the timer, it then plays on from there.
+    pub fn set_frame(&mut self, idx: usize) {
+        let idx = idx.min(self.frame_count() - 1);
+        if idx != self.frame {
+            self.entered.push(idx);
+        }
+        self.frame = idx;
+        self.elapsed = 0.;
+    }
+
+    /// Frames shown since the last call, including ones skipped over within a single update.
+    pub fn take_entered(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.entered)
+    }
+
+    /// Moves the animation on by `dt` seconds, returns `true` when the frame changed.
+    pub fn update(&mut self, dt: f32) -> bool {
+        if self.finished || self.speed <= 0. {
+            return false;
+        }
+
+        let start = self.frame;
+        self.elapsed += dt * self.speed;
+        while self.elapsed >= self.durations[self.frame] {
+            self.elapsed -= self.durations[self.frame];
+            if !self._step() {
+                self.elapsed = 0.;
+                break;
+            }
+            self.entered.push(self.frame);
+        }
+
+        self.frame != start
+    }
+
+    // Goes to the next frame, `false` once playback is over
+    fn _step(&mut self) -> bool {
+        let last = self.frame_count() - 1;
+        match self.mode {
+            LoopMode::Loop => self.frame = if self.frame == last { 0 } else { self.frame + 1 },
+            LoopMode::PingPong => {
+                if last == 0 {
+                    return true;
+                }
+                if (self.direction > 0 && self.frame == last) || (self.direction < 0 && self.frame == 0) {
+                    self.direction = -self.direction;
+                }
+                self.frame = (self.frame as i64 + self.direction as i64) as usize;
+            },
+            LoopMode::Once | LoopMode::Hold => {
+                if self.frame == last {
+                    self.finished = true;
+                    if self.mode == LoopMode::Once {
+                        self.frame = 0;
+                    }
+                    return false;
+                }
+                self.frame += 1;
+            }
+        }
+
+        true
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Frames shown after each of `ticks` updates of `dt`
+    fn frames(timer: &mut FrameTimer, dt: f32, ticks: usize) -> Vec<usize> {
+        (0..ticks).map(|_| {
+            timer.update(dt);
+            timer.frame()
+        }).collect()
+    }
+
+    #[test]
+    fn loop_modes() {
+        let mut timer = FrameTimer::with_durations(vec![0.1; 3]);
+        assert_eq!(frames(&mut timer, 0.1, 7), vec![1, 2, 0, 1, 2, 0, 1]);
+
+        timer.mode = LoopMode::PingPong;
+        timer.restart();
+        assert_eq!(frames(&mut timer, 0.1, 7), vec![1, 2, 1, 0, 1, 2, 1]);
+
+        timer.mode = LoopMode::Hold;
+        timer.restart();
+        assert_eq!(frames(&mut timer, 0.1, 4), vec![1, 2, 2, 2]);
+        assert!(timer.is_finished());
+
+        timer.mode = LoopMode::Once;
+        timer.restart();
+        assert_eq!(frames(&mut timer, 0.1, 4), vec![1, 2, 0, 0]);
+        assert!(timer.is_finished());
+        assert_eq!(timer.progress(), 1.);
+        assert!(!timer.update(1.));
+    }
+
+    #[test]
+    fn timing_follows_elapsed_time_not_updates() {
+        // Same 0.9 seconds of playback at 60 and 240 updates per second
+        let mut slow = FrameTimer::with_durations(vec![0.1, 0.3, 0.2]);
+        let mut fast = slow.clone();
+        frames(&mut slow, 1. / 60., 54);
+        frames(&mut fast, 1. / 240., 216);
+        assert_eq!((slow.frame(), fast.frame()), (1, 1));
+
+        // A long frame holds, a big step skips over several frames
+        let mut timer = FrameTimer::with_durations(vec![0.1, 0.3, 0.2]);
+        assert_eq!(frames(&mut timer, 0.1, 3), vec![1, 1, 1]);
+        assert_eq!(frames(&mut timer, 0.35, 1), vec![0]);
+
+        timer.restart();
+        frames(&mut timer, 0.2, 1);
+        assert!((timer.progress() - 1. / 3.).abs() < 1e-5);
+
+        timer.restart();
+        timer.speed = 2.;
+        assert_eq!(frames(&mut timer, 0.06, 3), vec![1, 1, 1]);
+        timer.speed = 0.;
+        assert!(!timer.update(10.));
+    }
+
+    #[test]
+    fn entered_frames_include_skipped_ones() {
+        let mut timer = FrameTimer::with_durations(vec![0.1; 4]);
+        assert_eq!(timer.take_entered(), vec![0]);
+
+        timer.update(0.25);
+        assert_eq!(timer.take_entered(), vec![1, 2]);
+        timer.update(0.01);
+        assert!(timer.take_entered().is_empty());
+
+        // Looping around enters the first frame again
+        timer.update(0.2);
+        assert_eq!(timer.take_entered(), vec![3, 0]);
+
+        timer.set_frame(0);
+        timer.set_frame(2);
+        timer.restart();
+        assert_eq!(timer.take_entered(), vec![2, 0]);
+    }
+
+    #[test]
+    fn zero_durations_do_not_stall() {
+        let mut timer = FrameTimer::with_durations(vec![0., 0.]);
+        timer.update(1.);
+        assert!(timer.frame() < 2);
+
+        let empty = FrameTimer::with_durations(Vec::new());
+        assert_eq!(empty.frame_count(), 1);
+    }
+}
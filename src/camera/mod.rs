@@ -0,0 +1,259 @@
+use std::collections::VecDeque;
+
+use ggez::{Context, GameResult};
+use ggez::graphics::{self, Rect};
+
+use glam::Vec2;
+use serde::Deserialize;
+
+use crate::consts::{
+    CAMERA_DEADZONE_WIDTH, CAMERA_DEADZONE_HEIGHT, CAMERA_LOOK_AHEAD,
+    CAMERA_LOOK_AHEAD_TIME, CAMERA_SMOOTHING, CAMERA_ZOOM, CAMERA_ZOOM_SPEED,
+    CAMERA_MAX_SHAKE, CAMERA_TRAUMA_DECAY, CHAR_SCALE_FACTOR
+};
+
+
+/// Tunable camera behaviour, in pixels and seconds.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct CameraParams {
+    // Box around the view center the target can move in without the camera following
+    pub deadzone_width: f32,
+    pub deadzone_height: f32,
+    // Furthest the view leads ahead of a running target
+    pub look_ahead: f32,
+    // Lead grows with speed, reaching this many seconds of travel
+    pub look_ahead_time: f32,
+    // How fast the view catches up, higher is snappier
+    pub smoothing: f32,
+    // Starting zoom, above 1 shows less of the level
+    pub zoom: f32,
+    pub zoom_speed: f32,
+    // Offset in screen pixels at full trauma
+    pub max_shake: f32,
+    // Trauma lost per second
+    pub trauma_decay: f32
+}
+
+impl Default for CameraParams {
+    fn default() -> Self {
+        Self {
+            deadzone_width: CAMERA_DEADZONE_WIDTH,
+            deadzone_height: CAMERA_DEADZONE_HEIGHT,
+            look_ahead: CAMERA_LOOK_AHEAD,
+            look_ahead_time: CAMERA_LOOK_AHEAD_TIME,
+            smoothing: CAMERA_SMOOTHING,
+            zoom: CAMERA_ZOOM,
+            zoom_speed: CAMERA_ZOOM_SPEED,
+            max_shake: CAMERA_MAX_SHAKE,
+            trauma_decay: CAMERA_TRAUMA_DECAY
+        }
+    }
+}
+
+
+// Scripted move to a fixed point, e.g. to show a boss before the fight
+struct Pan {
+    to: Vec2,
+    duration: f32,
+    // Time spent at `to` before the next pan or before following again
+    hold: f32,
+    from: Option<Vec2>,
+    elapsed: f32
+}
+
+
+/// View into a level larger than the screen. World drawing happens between
+/// `apply` and `reset`, everything else stays in screen space.
+pub struct Camera {
+    pub params: CameraParams,
+    // Center of the view in world space
+    center: Vec2,
+    prev_center: Vec2,
+    look_ahead: f32,
+    zoom: f32,
+    prev_zoom: f32,
+    target_zoom: f32,
+    // In [0, 1], shake grows with its square
+    trauma: f32,
+    shake: Vec2,
+    prev_shake: Vec2,
+    time: f32,
+    pans: VecDeque<Pan>,
+    screen_size: Vec2,
+    bounds: Rect,
+    // Screen coordinates to restore in `reset`
+    screen: Rect
+}
+
+impl Camera {
+    pub fn new(_ctx: &mut Context, params: CameraParams, bounds: Rect) -> Self {
+        let screen = graphics::screen_coordinates(_ctx);
+        let zoom = snap_zoom(params.zoom);
+        Self {
+            params: params,
+            center: Vec2::new(screen.w / 2., screen.h / 2.),
+            prev_center: Vec2::new(screen.w / 2., screen.h / 2.),
+            look_ahead: 0.,
+            zoom: zoom,
+            prev_zoom: zoom,
+            target_zoom: zoom,
+            trauma: 0.,
+            shake: Vec2::ZERO,
+            prev_shake: Vec2::ZERO,
+            time: 0.,
+            pans: VecDeque::new(),
+            screen_size: Vec2::new(screen.w, screen.h),
+            bounds: bounds,
+            screen: screen
+        }
+    }
+
+    /// Jumps straight to `target`, e.g. when a level starts.
+    pub fn snap_to(&mut self, target: Rect) {
+        self.look_ahead = 0.;
+        self.center = self._clamp(target.center().into());
+        self.prev_center = self.center;
+    }
+
+    /// Adds to the trauma driving the screen shake, capped at 1.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.);
+    }
+
+    /// Eases towards `zoom`, rounded so sprite pixels stay whole screen pixels.
+    pub fn zoom_to(&mut self, zoom: f32) {
+        self.target_zoom = snap_zoom(zoom);
+    }
+
+    /// Queues a pan to `to` taking `duration` seconds, then stays there for `hold` seconds.
+    /// Following resumes once every queued pan is done.
+    pub fn pan(&mut self, to: Vec2, duration: f32, hold: f32) {
+        self.pans.push_back(Pan {
+            to: to,
+            duration: duration,
+            hold: hold,
+            from: None,
+            elapsed: 0.
+        });
+    }
+
+    /// Moves the view towards `target` once it leaves the deadzone, leading in the direction of `velocity`.
+    pub fn follow(&mut self, _ctx: &mut Context, target: Rect, velocity: Vec2, dt: f32) {
+        let screen = graphics::screen_coordinates(_ctx);
+        self.screen_size = Vec2::new(screen.w, screen.h);
+        self.prev_center = self.center;
+        self.prev_zoom = self.zoom;
+        self.prev_shake = self.shake;
+        self.time += dt;
+
+        self.zoom += (self.target_zoom - self.zoom) * (1. - (-self.params.zoom_speed * dt).exp());
+        if (self.target_zoom - self.zoom).abs() < 0.001 {
+            self.zoom = self.target_zoom;
+        }
+
+        if let Some(center) = self._advance_pan(dt) {
+            self.center = self._clamp(center);
+        } else {
+            let lead = (velocity.x * self.params.look_ahead_time).clamp(-self.params.look_ahead, self.params.look_ahead);
+            self.look_ahead += (lead - self.look_ahead) * self._blend(dt);
+
+            let focus = Vec2::from(target.center()) + Vec2::new(self.look_ahead, 0.);
+            let half_deadzone = Vec2::new(self.params.deadzone_width, self.params.deadzone_height) / 2.;
+
+            // Only the part of the focus outside of the deadzone pulls the view
+            let offset = focus - self.center;
+            let pull = offset - offset.clamp(-half_deadzone, half_deadzone);
+
+            self.center = self._clamp(self.center + pull * self._blend(dt));
+        }
+
+        self.trauma = (self.trauma - self.params.trauma_decay * dt).max(0.);
+        let strength = self.trauma * self.trauma * self.params.max_shake / self.zoom;
+        self.shake = Vec2::new(noise(self.time, 0.), noise(self.time, 1.)) * strength;
+    }
+
+    /// Top left corner of the view blended between the last two ticks, on the screen's pixel grid.
+    pub fn position(&self, alpha: f32) -> Vec2 {
+        let zoom = self._zoom(alpha);
+        let center = self.prev_center + (self.center - self.prev_center) * alpha;
+        let shake = self.prev_shake + (self.shake - self.prev_shake) * alpha;
+        let position = center + shake - self.screen_size / zoom / 2.;
+
+        // With nearest filtering, a view between pixels makes sprites shimmer
+        (position * zoom).round() / zoom
+    }
+
+    /// Visible part of the world.
+    pub fn view_rect(&self, alpha: f32) -> Rect {
+        let position = self.position(alpha);
+        let view = self.screen_size / self._zoom(alpha);
+        Rect::new(position.x, position.y, view.x, view.y)
+    }
+
+    pub fn apply(&mut self, _ctx: &mut Context, alpha: f32) -> GameResult<()> {
+        self.screen = graphics::screen_coordinates(_ctx);
+        graphics::set_screen_coordinates(_ctx, self.view_rect(alpha))
+    }
+
+    pub fn reset(&self, _ctx: &mut Context) -> GameResult<()> {
+        graphics::set_screen_coordinates(_ctx, self.screen)
+    }
+
+    // Center of the active pan this tick, `None` once all pans are done
+    fn _advance_pan(&mut self, dt: f32) -> Option<Vec2> {
+        let current = self.center;
+        let pan = self.pans.front_mut()?;
+        let from = *pan.from.get_or_insert(current);
+
+        pan.elapsed += dt;
+        let t = if pan.duration > 0. { (pan.elapsed / pan.duration).min(1.) } else { 1. };
+        let eased = t * t * (3. - 2. * t);
+        let center = from + (pan.to - from) * eased;
+
+        if pan.elapsed >= pan.duration + pan.hold {
+            self.pans.pop_front();
+        }
+
+        Some(center)
+    }
+
+    fn _zoom(&self, alpha: f32) -> f32 {
+        self.prev_zoom + (self.zoom - self.prev_zoom) * alpha
+    }
+
+    // Fraction of the remaining distance covered this tick, independent of the tick rate
+    fn _blend(&self, dt: f32) -> f32 {
+        1. - (-self.params.smoothing * dt).exp()
+    }
+
+    // Keeps the view inside the level, centering it when the level is smaller than the screen
+    fn _clamp(&self, center: Vec2) -> Vec2 {
+        let half = self.screen_size / self.zoom / 2.;
+        let axis = |value: f32, min: f32, size: f32, half: f32| {
+            if size <= half * 2. {
+                min + size / 2.
+            } else {
+                value.clamp(min + half, min + size - half)
+            }
+        };
+
+        Vec2::new(
+            axis(center.x, self.bounds.x, self.bounds.w, half.x),
+            axis(center.y, self.bounds.y, self.bounds.h, half.y)
+        )
+    }
+}
+
+
+// Sprites are drawn at CHAR_SCALE_FACTOR, so zooming in steps of its inverse keeps their pixels square
+fn snap_zoom(zoom: f32) -> f32 {
+    let step = 1. / CHAR_SCALE_FACTOR;
+    ((zoom / step).round() * step).max(step)
+}
+
+
+// Smooth pseudo random value in [-1, 1], a different curve per `seed`
+fn noise(time: f32, seed: f32) -> f32 {
+    (time * 37. + seed * 11.3).sin() * 0.6 + (time * 61. + seed * 5.7).sin() * 0.4
+}
@@ -1,21 +1,47 @@
 use ggez::Context;
 
-use crate::character::{Character, CharacterAnimation};
-use crate::animation::SpriteAnimation;
-use crate::utils::join_paths;
+use serde::Deserialize;
+
+use crate::character::Character;
 use crate::consts::{PUNK_DIR, BIKER_DIR, CYBORG_DIR};
 
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum CharacterKind {
+    Punk,
+    Biker,
+    Cyborg
+}
+
+impl CharacterKind {
+    pub const ALL: [CharacterKind; 3] = [CharacterKind::Punk, CharacterKind::Biker, CharacterKind::Cyborg];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CharacterKind::Punk => "Punk",
+            CharacterKind::Biker => "Biker",
+            CharacterKind::Cyborg => "Cyborg"
+        }
+    }
+
+    pub fn build(&self, _ctx: &mut Context) -> Character {
+        match self {
+            CharacterKind::Punk => Punk::new(_ctx),
+            CharacterKind::Biker => Biker::new(_ctx),
+            CharacterKind::Cyborg => Cyborg::new(_ctx)
+        }
+    }
+}
+
+
 pub struct Punk;
 
 impl Punk {
     pub fn new(_ctx: &mut Context) -> Character {
-        let mut punk = Character::default(_ctx);
-        punk.insert_animation(CharacterAnimation::Idle, SpriteAnimation::new(_ctx, &join_paths(&PUNK_DIR, "Punk_idle.png")));
-        punk.insert_animation(CharacterAnimation::Run, SpriteAnimation::new(_ctx, &join_paths(&PUNK_DIR, "Punk_run.png")));
-        punk.insert_animation(CharacterAnimation::Jump,  SpriteAnimation::new(_ctx, &join_paths(&PUNK_DIR, "Punk_jump.png")));
-        punk.insert_animation(CharacterAnimation::Attack, SpriteAnimation::new(_ctx, &join_paths(&PUNK_DIR, "Punk_attack1.png")));
-        punk.insert_animation(CharacterAnimation::DoubleJump, SpriteAnimation::new(_ctx, &join_paths(&PUNK_DIR, "Punk_doublejump.png")));
+        let mut punk = Character::new(_ctx, PUNK_DIR).unwrap();
+
+        // Most agile of the three
+        punk.body.params.air_jumps = 2;
 
         return punk;
     }
@@ -26,12 +52,7 @@ pub struct Biker;
 
 impl Biker {
     pub fn new(_ctx: &mut Context) -> Character {
-        let mut biker = Character::default(_ctx);
-        biker.insert_animation(CharacterAnimation::Idle, SpriteAnimation::new(_ctx, &join_paths(&BIKER_DIR, "Biker_idle.png")));
-        biker.insert_animation(CharacterAnimation::Run, SpriteAnimation::new(_ctx, &join_paths(&BIKER_DIR, "Biker_run.png")));
-        biker.insert_animation(CharacterAnimation::Jump,  SpriteAnimation::new(_ctx, &join_paths(&BIKER_DIR, "Biker_jump.png")));
-        biker.insert_animation(CharacterAnimation::Attack, SpriteAnimation::new(_ctx, &join_paths(&BIKER_DIR, "Biker_attack.png")));
-        biker.insert_animation(CharacterAnimation::DoubleJump, SpriteAnimation::new(_ctx, &join_paths(&BIKER_DIR, "Biker_doublejump.png")));
+        let biker = Character::new(_ctx, BIKER_DIR).unwrap();
 
         return biker;
     }
@@ -42,12 +63,11 @@ pub struct Cyborg;
 
 impl Cyborg {
     pub fn new(_ctx: &mut Context) -> Character {
-        let mut cyborg = Character::default(_ctx);
-        cyborg.insert_animation(CharacterAnimation::Idle, SpriteAnimation::new(_ctx, &join_paths(&CYBORG_DIR, "Cyborg_idle.png")));
-        cyborg.insert_animation(CharacterAnimation::Run, SpriteAnimation::new(_ctx, &join_paths(&CYBORG_DIR, "Cyborg_run.png")));
-        cyborg.insert_animation(CharacterAnimation::Jump,  SpriteAnimation::new(_ctx, &join_paths(&CYBORG_DIR, "Cyborg_jump.png")));
-        cyborg.insert_animation(CharacterAnimation::Attack, SpriteAnimation::new(_ctx, &join_paths(&CYBORG_DIR, "Cyborg_attack.png")));
-        cyborg.insert_animation(CharacterAnimation::DoubleJump, SpriteAnimation::new(_ctx, &join_paths(&CYBORG_DIR, "Cyborg_doublejump.png")));
+        let mut cyborg = Character::new(_ctx, CYBORG_DIR).unwrap();
+
+        // Heavier than the others: jumps lower and takes longer to get going
+        cyborg.body.params.jump_height = 150.;
+        cyborg.body.params.run_acceleration = 1800.;
 
         return cyborg;
     }
@@ -0,0 +1,300 @@
+use std::io::Read;
+
+use ggez::{Context, GameError, GameResult};
+use ggez::filesystem;
+
+use glam::Vec2;
+use serde::Deserialize;
+
+use crate::animation::{load_sidecar, LoopMode, SpriteAnimation};
+use crate::input::{Action, InputState};
+use crate::utils::join_paths;
+use crate::consts::CHAR_ANIMATIONS_FILE;
+
+
+/// What a transition looks at, all conditions of a transition have to hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    Grounded,
+    Airborne,
+    // Horizontal velocity
+    Moving,
+    Still,
+    // Vertical velocity
+    Rising,
+    Falling,
+    // Used up an air jump this tick
+    AirJumped,
+    Pressed(Action),
+    JustPressed(Action)
+}
+
+impl Condition {
+    pub fn holds(&self, situation: &Situation) -> bool {
+        match self {
+            Condition::Grounded => situation.grounded,
+            Condition::Airborne => !situation.grounded,
+            Condition::Moving => situation.velocity.x != 0.,
+            Condition::Still => situation.velocity.x == 0.,
+            Condition::Rising => situation.velocity.y < 0.,
+            Condition::Falling => situation.velocity.y > 0.,
+            Condition::AirJumped => situation.air_jumped,
+            Condition::Pressed(action) => situation.input.is_pressed(*action),
+            Condition::JustPressed(action) => situation.input.is_just_pressed(*action)
+        }
+    }
+}
+
+
+/// Character state the conditions are checked against, taken once per tick.
+pub struct Situation<'a> {
+    pub grounded: bool,
+    pub velocity: Vec2,
+    pub air_jumped: bool,
+    pub input: &'a InputState
+}
+
+
+/// What picks the frame of a state's animation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Driver {
+    Timer,
+    // Take-off frames going up, landing frames coming down
+    VerticalSpeed
+}
+
+impl Default for Driver {
+    fn default() -> Self {
+        Driver::Timer
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StateTransition {
+    pub to: String,
+    #[serde(default)]
+    pub when: Vec<Condition>,
+    // Share of the animation that has to have played, 1 waits for a one shot animation to finish
+    #[serde(default)]
+    pub exit_time: f32
+}
+
+impl StateTransition {
+    fn fires(&self, progress: f32, situation: &Situation) -> bool {
+        progress >= self.exit_time && self.when.iter().all(|condition| condition.holds(situation))
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AnimationState {
+    pub name: String,
+    // Relative to the character's directory
+    pub image: String,
+    // Plays only these frames of the image's sheet
+    pub tag: Option<String>,
+    // Replaces the mode of the sheet
+    pub mode: Option<LoopMode>,
+    pub frame_time: Option<f32>,
+    #[serde(default)]
+    pub driver: Driver,
+    // Whether the graph's `any` transitions can cut the state short
+    #[serde(default = "default_interruptible")]
+    pub interruptible: bool,
+    // Keeps the character from walking or jumping off while grounded
+    #[serde(default)]
+    pub rooted: bool,
+    // Checked in order, the first one that fires wins
+    #[serde(default)]
+    pub transitions: Vec<StateTransition>
+}
+
+impl AnimationState {
+    /// Loads the state's animation from the character directory `dir`.
+    pub fn animation(&self, _ctx: &mut Context, dir: &str) -> GameResult<SpriteAnimation> {
+        let path = join_paths(dir, &self.image);
+        let mut animation = match &self.tag {
+            Some(tag) => {
+                let sheet = load_sidecar(_ctx, &path)
+                    .unwrap_or_else(|| Err(GameError::ResourceLoadError(format!("{} has no frame metadata", path))))?;
+                SpriteAnimation::from_sheet(_ctx, &sheet, Some(tag))?
+            },
+            None => SpriteAnimation::new(_ctx, &path)
+        };
+
+        if let Some(mode) = self.mode {
+            animation.timer.mode = mode;
+        }
+        if let Some(frame_time) = self.frame_time {
+            animation.timer.set_frame_time(frame_time);
+        }
+        Ok(animation)
+    }
+}
+
+fn default_interruptible() -> bool {
+    true
+}
+
+
+/// Which animation a character plays and when it switches, read from `<character dir>/CHAR_ANIMATIONS_FILE`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AnimationGraph {
+    pub initial: String,
+    // Checked from every interruptible state before its own transitions.
+    // Going to the current state starts it over
+    #[serde(default)]
+    pub any: Vec<StateTransition>,
+    pub states: Vec<AnimationState>
+}
+
+impl AnimationGraph {
+    pub fn load(_ctx: &mut Context, dir: &str) -> GameResult<Self> {
+        let path = join_paths(dir, CHAR_ANIMATIONS_FILE);
+        let mut file = filesystem::open(_ctx, &path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        Self::parse(&contents).map_err(|e| GameError::ConfigError(format!("{}: {}", path, e)))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let graph: AnimationGraph = toml::from_str(contents).map_err(|e| e.to_string())?;
+
+        for (idx, state) in graph.states.iter().enumerate() {
+            if graph.states[..idx].iter().any(|other| other.name == state.name) {
+                return Err(format!("state {} is defined twice", state.name));
+            }
+        }
+        if graph.index(&graph.initial).is_none() {
+            return Err(format!("initial state {} does not exist", graph.initial));
+        }
+        let transitions = graph.any.iter().chain(graph.states.iter().flat_map(|state| state.transitions.iter()));
+        for transition in transitions {
+            if graph.index(&transition.to).is_none() {
+                return Err(format!("transition to {}, which does not exist", transition.to));
+            }
+        }
+
+        Ok(graph)
+    }
+
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|state| state.name == name)
+    }
+
+    pub fn initial(&self) -> usize {
+        self.index(&self.initial).unwrap()
+    }
+
+    /// State to go to from `current`, whose animation has played `progress` of the way, if any.
+    pub fn next(&self, current: usize, progress: f32, situation: &Situation) -> Option<usize> {
+        let state = &self.states[current];
+        let any = if state.interruptible { &self.any[..] } else { &[] };
+
+        any.iter().chain(state.transitions.iter())
+            .find(|transition| transition.fires(progress, situation))
+            .map(|transition| self.index(&transition.to).unwrap())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ggez::event::KeyCode;
+
+    use crate::input::{Binding, InputConfig};
+
+    const GRAPH: &str = r#"
+        initial = "idle"
+        any = [{ to = "flip", when = ["air_jumped"] }]
+
+        [[states]]
+        name = "idle"
+        image = "idle.png"
+        transitions = [
+            { to = "attack", when = [{ pressed = "Attack" }] },
+            { to = "run", when = ["grounded", "moving"] }
+        ]
+
+        [[states]]
+        name = "run"
+        image = "run.png"
+        frame_time = 0.12
+
+        [[states]]
+        name = "flip"
+        image = "flip.png"
+        mode = "once"
+        driver = "vertical_speed"
+
+        [[states]]
+        name = "attack"
+        image = "attack.png"
+        interruptible = false
+        rooted = true
+        transitions = [{ to = "idle", exit_time = 1 }]
+    "#;
+
+    #[test]
+    fn picks_the_first_transition_that_fires() {
+        let graph = AnimationGraph::parse(GRAPH).unwrap();
+        let (idle, run, flip, attack) = (0, 1, 2, 3);
+        assert_eq!(graph.initial(), idle);
+        assert_eq!((graph.states[run].frame_time, graph.states[flip].mode), (Some(0.12), Some(LoopMode::Once)));
+        assert_eq!(graph.states[flip].driver, Driver::VerticalSpeed);
+        assert!(graph.states[attack].rooted && !graph.states[attack].interruptible);
+
+        let mut input = InputState::with_config(InputConfig::default());
+        let mut situation = Situation { grounded: true, velocity: Vec2::ZERO, air_jumped: false, input: &input };
+        assert_eq!(graph.next(idle, 0., &situation), None);
+        assert_eq!(graph.next(run, 0., &situation), None);
+
+        situation.velocity.x = 10.;
+        assert_eq!(graph.next(idle, 0., &situation), Some(run));
+        // Air jumps go through `any`, even back into the same state
+        situation.air_jumped = true;
+        assert_eq!(graph.next(idle, 0., &situation), Some(flip));
+        assert_eq!(graph.next(flip, 0.5, &situation), Some(flip));
+
+        // Attacks can't be interrupted and end once played through
+        assert_eq!(graph.next(attack, 0.5, &situation), None);
+        assert_eq!(graph.next(attack, 1., &situation), Some(idle));
+
+        input.press(Binding::Key(KeyCode::F));
+        input.tick();
+        let situation = Situation { grounded: true, velocity: Vec2::new(10., 0.), air_jumped: false, input: &input };
+        assert_eq!(graph.next(idle, 0., &situation), Some(attack));
+    }
+
+    #[test]
+    fn rejects_unknown_states() {
+        assert!(AnimationGraph::parse(&GRAPH.replace("to = \"run\"", "to = \"walk\"")).is_err());
+        assert!(AnimationGraph::parse(&GRAPH.replace("initial = \"idle\"", "initial = \"sleep\"")).is_err());
+        assert!(AnimationGraph::parse(&GRAPH.replace("name = \"run\"", "name = \"idle\"")).is_err());
+    }
+
+    #[test]
+    fn shipped_graphs_parse() {
+        let chars = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/chars");
+        for entry in std::fs::read_dir(chars).unwrap() {
+            let dir = entry.unwrap().path();
+            let contents = std::fs::read_to_string(dir.join(CHAR_ANIMATIONS_FILE)).unwrap();
+            let graph = match AnimationGraph::parse(&contents) {
+                Ok(graph) => graph,
+                Err(e) => panic!("{}: {}", dir.display(), e)
+            };
+            for state in &graph.states {
+                assert!(dir.join(&state.image).exists(), "{}", state.image);
+                // Once goes back to the first frame as it finishes, which would show for a tick
+                let waits = state.transitions.iter().any(|transition| transition.exit_time >= 1.);
+                assert!(!(waits && state.mode == Some(LoopMode::Once)), "{} plays once", state.name);
+            }
+        }
+    }
+}
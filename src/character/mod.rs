@@ -1,51 +1,31 @@
-use std::cell::{RefCell, RefMut};
-use std::collections::HashMap;
-
 use ggez::{Context, GameResult};
-use ggez::event::KeyCode;
-use ggez::input::keyboard;
 use ggez::graphics::{self, *};
 
 use glam::Vec2;
 
 use crate::base::CustomRect;
-use crate::quadtree::QuadTree;
-use crate::animation::SpriteAnimation;
-use crate::collisions::{rect_collision, SideCollided};
-use crate::consts::{
-    CHAR_WIDTH, RUN_SPEED, JUMP_SPEED_DY, 
-    JUMP_SPEED_DX, CHAR_SCALE_FACTOR
-};
+use crate::animation::{AnimationEvent, SpriteAnimation};
+use crate::input::{Action, InputState};
+use crate::physics::{Body, PhysicsParams};
+use crate::collisions::{min_translation, overlap_area, sweep, SideCollided, ColliderId, CollisionWorld, Layer};
+use crate::consts::{ATTACK_REACH, CHAR_WIDTH, CHAR_SCALE_FACTOR, MAX_SWEEP_STEPS, PLATFORM_TOLERANCE};
 
 
 
 pub mod chars;
 
-
-#[derive(Debug, PartialEq, Eq, Hash)]
-pub enum CharacterAnimation {
-    Idle,
-    Run,
-    Jump,
-    DoubleJump,
-    Attack
-}
+pub mod graph;
+pub use graph::{AnimationGraph, Driver, Situation};
 
 
 pub struct CharacterState {
-    is_flipped: bool,
-    jumping_right: bool,
-    jumping_left: bool,
-    falling: bool
+    is_flipped: bool
 }
 
 impl CharacterState {
     pub fn default() -> Self {
         Self {
-            is_flipped: false,
-            jumping_right: false,
-            jumping_left: false,
-            falling: true
+            is_flipped: false
         }
     }
 }
@@ -55,67 +35,86 @@ pub struct Layout {
     x: f32,
     y: f32,
     w: f32,
-    h: f32
+    h: f32,
+    prev_x: f32,
+    prev_y: f32
 }
 
 impl Layout {
     pub fn default(_ctx: &mut Context) -> Self {
-        let (_, h) = graphics::size(_ctx);
         Self {
             x: 100.,
             y: 100.,
             w: CHAR_WIDTH,
-            h: CHAR_WIDTH
+            h: CHAR_WIDTH,
+            prev_x: 100.,
+            prev_y: 100.
         }
     }
+
+    /// Position blended between the previous and the current simulation tick.
+    pub fn interpolated(&self, alpha: f32) -> Vec2 {
+        Vec2::new(
+            self.prev_x + (self.x - self.prev_x) * alpha,
+            self.prev_y + (self.y - self.prev_y) * alpha
+        )
+    }
 }
 
 
 pub struct Character {
-    animations: HashMap<CharacterAnimation, RefCell<SpriteAnimation>>,
+    graph: AnimationGraph,
+    // One per state of `graph`
+    animations: Vec<SpriteAnimation>,
     layout: Layout,
     state: CharacterState,
-    current: CharacterAnimation,
-    pub quadtree: QuadTree
+    // State of `graph` playing
+    current: usize,
+    pub body: Body,
+    // Hitbox registered in the level's collision world
+    pub collider: Option<ColliderId>,
+    // Fired by the current animation during the last update
+    events: Vec<AnimationEvent>,
+    // Between the hit_start and hit_end of an attack
+    hitting: bool
 }
 
 impl Character {
 
-    pub fn default(_ctx: &mut Context) -> Self {
-        let (w, h) = graphics::size(_ctx);
-        Self {
-            animations: HashMap::new(),
+    /// Character animated by the state graph in `dir`.
+    pub fn new(_ctx: &mut Context, dir: &str) -> GameResult<Self> {
+        let graph = AnimationGraph::load(_ctx, dir)?;
+        let animations = graph.states.iter()
+            .map(|state| state.animation(_ctx, dir))
+            .collect::<GameResult<Vec<SpriteAnimation>>>()?;
+
+        Ok(Self {
+            current: graph.initial(),
+            graph: graph,
+            animations: animations,
             layout: Layout::default(_ctx),
             state: CharacterState::default(),
-            current: CharacterAnimation::Idle,
-            quadtree: QuadTree::new(0., 0., w, h)
-        }
-    }
-
-    pub fn insert_animation(&mut self, animation: CharacterAnimation, sprite: SpriteAnimation) {
-        self.animations.insert(animation, RefCell::new(sprite));
+            body: Body::new(PhysicsParams::default()),
+            collider: None,
+            events: Vec::new(),
+            hitting: false
+        })
     }
 
-
-    pub fn perform_action(&self, mut animation: RefMut<'_, SpriteAnimation>) {
-        if !animation.performing {
-            animation.performing = true;
-        } else if animation.image_idx == animation.image_count {
-            animation.performing = false;
-            animation.image_idx = 0;
+    pub fn param(&self, animation: &SpriteAnimation, alpha: f32) -> graphics::DrawParam{
+        // Keep the frame's pivot on the bottom center of the sprite box, so
+        // trimmed or differently sized frames don't make the character jump around
+        let frame = animation.frame();
+        let size = CHAR_WIDTH / CHAR_SCALE_FACTOR;
+        let mut shift = (Vec2::new(size / 2., size) - frame.pivot + frame.offset) * CHAR_SCALE_FACTOR;
+        if self.state.is_flipped {
+            shift.x = -shift.x;
         }
-    }
 
-    pub fn param(&self, src_x: f32, w: f32) -> graphics::DrawParam{
         let mut params = graphics::DrawParam::default()
-            .src(graphics::Rect {
-                x: src_x,
-                y: 0f32,
-                w: w,
-                h: 1f32
-            })
-            .dest(Vec2::new(self.layout.x, self.layout.y));
-            
+            .src(animation.src())
+            .dest(self.layout.interpolated(alpha) + shift);
+
         if self.state.is_flipped {
             params = params.scale(Vec2::new(-CHAR_SCALE_FACTOR, CHAR_SCALE_FACTOR));
         } else {
@@ -125,177 +124,288 @@ impl Character {
         return params;
     }
 
-    fn run_right(&mut self, _ctx: &mut Context) {
-        self.current = CharacterAnimation::Run;
-        let mut current_anim = self.animations.get(&self.current).unwrap().borrow_mut();
-        current_anim.src_x = current_anim.next_x();
-        if self.state.is_flipped {
-            self.state.is_flipped = false;
-            self.layout.x -= self.layout.w / 4.;
+    // The sprite flips around its left edge, shift it so the character stays in place
+    fn face(&mut self, flipped: bool) {
+        if self.state.is_flipped == flipped {
+            return;
         }
 
-        self.layout.x += RUN_SPEED;
-        let (width, _) = graphics::size(_ctx);
-        self.layout.x = if self.layout.x + self.layout.w / 2. <= width {
-            self.layout.x
-        } else {
-            width - self.layout.w
-        };
+        // The previous position moves along, so the shift isn't interpolated over a frame
+        let shift = if flipped { self.layout.w / 4. } else { -self.layout.w / 4. };
+        self.state.is_flipped = flipped;
+        self.layout.x += shift;
+        self.layout.prev_x += shift;
+    }
+
+    // Starts `state` over, also when it is already playing
+    fn enter(&mut self, state: usize) {
+        self.current = state;
+        self.animations[state].restart();
+        // An attack cut short never reaches its hit_end
+        self.hitting = false;
     }
 
-    fn run_left(&mut self, _ctx: &mut Context) {
-        self.current = CharacterAnimation::Run;
-        let mut current_anim = self.animations.get(&self.current).unwrap().borrow_mut();
-        current_anim.src_x = current_anim.next_x();
-        if !self.state.is_flipped {
-            self.state.is_flipped = true;
-            self.layout.x += self.layout.w / 4.;
+    fn animate(&mut self, dt: f32) {
+        let jump_velocity = self.body.jump_velocity();
+        let animation = &mut self.animations[self.current];
+        match self.graph.states[self.current].driver {
+            Driver::Timer => {
+                animation.update(dt);
+            },
+            Driver::VerticalSpeed => {
+                let progress = (self.body.velocity.y / jump_velocity + 1.) / 2.;
+                let frame = (progress.clamp(0., 1.) * (animation.timer.frame_count() - 1) as f32).round() as usize;
+                animation.set_frame(frame);
+            }
         }
+    }
 
-        self.layout.x -= RUN_SPEED;
-        self.layout.x = if self.layout.x - self.layout.w / 2. >= 0. {
-            self.layout.x
-        } else {
-            self.layout.w / 2.
-        };
+    /// World space rect the character collides with.
+    pub fn hitbox(&self) -> Rect {
+        let mut rect = Rect::new(self.layout.x, self.layout.y, self.layout.w, self.layout.h);
+        if self.state.is_flipped {
+            rect.x -= self.layout.w / 4.;
+        }
+        rect
     }
 
-    fn idle(&mut self, _ctx: &mut Context) {
-        self.current = CharacterAnimation::Idle;
-        let mut idle_anim = self.animations.get(&self.current).unwrap().borrow_mut();
-        idle_anim.src_x = idle_anim.next_x();
+    /// Animation events fired during the last update, in order.
+    pub fn events(&self) -> &[AnimationEvent] {
+        &self.events
     }
 
-    fn perform_jump(&mut self, _ctx: &mut Context) {
-        self.current = CharacterAnimation::Jump;
-        let mut current_anim = self.animations.get(&self.current).unwrap().borrow_mut();
-        if current_anim.image_idx * 2 < current_anim.image_count {
-            self.layout.y -= JUMP_SPEED_DY;
-        } else {
-            self.layout.y += JUMP_SPEED_DY;
+    /// Area the attack can hurt, only while the attack is between its hit_start and hit_end frames.
+    pub fn attack_box(&self) -> Option<Rect> {
+        if !self.hitting {
+            return None;
         }
 
-        if self.state.jumping_right {
-            self.layout.x += JUMP_SPEED_DX;
-        } else if self.state.jumping_left {
-            self.layout.x -= JUMP_SPEED_DX;
+        let hitbox = self.hitbox();
+        let w = hitbox.w / 2. + ATTACK_REACH;
+        let x = if self.state.is_flipped { hitbox.left() - ATTACK_REACH } else { hitbox.x + hitbox.w / 2. };
+        Some(Rect::new(x, hitbox.y, w, hitbox.h))
+    }
+
+    // Keeps the events of the current animation, the others are dropped so they don't fire late
+    fn collect_events(&mut self) {
+        self.events.clear();
+        for (idx, animation) in self.animations.iter_mut().enumerate() {
+            let events = animation.take_events();
+            if idx == self.current {
+                self.events = events;
+            }
         }
 
-        current_anim.src_x = current_anim.next_x();
-        self.perform_action(current_anim);
+        for event in &self.events {
+            match event {
+                AnimationEvent::HitStart => self.hitting = true,
+                AnimationEvent::HitEnd => self.hitting = false,
+                _ => {}
+            }
+        }
     }
 
-    fn perform_attack(&mut self, _ctx: &mut Context) {
-        self.current = CharacterAnimation::Attack;
-        let mut current_anim = self.animations.get(&self.current).unwrap().borrow_mut();
-        current_anim.src_x = current_anim.next_x();
-        self.perform_action(current_anim);
+    /// Moves the character so the bottom left of its hitbox is at (`x`, `y`), i.e. it stands on `y`.
+    pub fn spawn_at(&mut self, x: f32, y: f32) {
+        self.layout.x = x;
+        self.layout.y = y - self.layout.h;
+        self.layout.prev_x = self.layout.x;
+        self.layout.prev_y = self.layout.y;
     }
 
-    // fn perform_double_jump(&mut self, _ctx: &mut Context) {
-    //     self.current = CharacterAnimation::DoubleJump;
-    //     let mut current_anim = self.animations.get(&self.current).unwrap().borrow_mut();
-    //     current_anim.src_x = current_anim.next_x();
-    //     self.perform_action(current_anim);
-    // }
+    /// Adds the hitbox to `world` on `layer`, it follows the character from then on.
+    pub fn register(&mut self, world: &mut CollisionWorld, layer: Layer) {
+        self.collider = Some(world.add(self.hitbox(), layer, layer.default_mask()));
+    }
 
-    pub fn update(&mut self, _ctx: &mut Context) -> GameResult<()>{
-        if !self.state.falling {
-            self._update(_ctx).unwrap();
-        } else {
-            self.layout.y += 50.;
-        }
+    // Terrain overlapping or touching `area`, unless the character's mask ignores terrain.
+    // Platforms only count while the character is above them
+    fn nearby_tiles(&self, world: &CollisionWorld, area: &Rect) -> Vec<Rect> {
+        let solid = Layer::TERRAIN | Layer::PLATFORM;
+        let mask = match self.collider.and_then(|id| world.collider(id)) {
+            Some(collider) => collider.mask & solid,
+            None => solid
+        };
 
-        let mut char_rect = CustomRect::new(
-            self.layout.x,
-            self.layout.y,
-            self.layout.w,
-            self.layout.h
-        );
-        if self.state.is_flipped {
-            char_rect.fields.x -= self.layout.w / 4.;
-        }
-        let data = self.quadtree.search(self.layout.x, self.layout.y);
-        if data.is_some() {
-            for loc in data.unwrap() {
-                let side = rect_collision(&char_rect.fields, loc);
-                if side.is_some() {
-                    match side.unwrap() {
-                        SideCollided::Top => {
-                            if self.state.falling {
-                                self.layout.y = loc.y - self.layout.h;
-                            }
-                            self.state.falling = false;
-                        }
-                        _ => ()
-                    }
+        let feet = self.hitbox().bottom();
+        world.query(area, mask, self.collider).into_iter()
+            .filter(|(id, bounds)| {
+                let platform = world.collider(*id).map_or(false, |collider| collider.layer == Layer::PLATFORM);
+                !platform || feet <= bounds.top() + PLATFORM_TOLERANCE
+            })
+            .map(|(_, bounds)| bounds)
+            .collect()
+    }
+
+    fn touch(&mut self, side: SideCollided) {
+        match side {
+            SideCollided::Top => {
+                if self.body.velocity.y >= 0. {
+                    self.body.land();
                 }
+            },
+            SideCollided::Bottom => self.body.hit_ceiling(),
+            SideCollided::Left | SideCollided::Right => self.body.hit_wall()
+        }
+    }
+
+    /// Moves the character by `displacement` with swept AABB tests, stopping at the
+    /// first tile in the way and sliding along it with whatever movement is left.
+    /// No matter how fast the character goes it can't skip over a tile.
+    fn sweep_move(&mut self, world: &CollisionWorld, mut displacement: Vec2) {
+        for _ in 0..MAX_SWEEP_STEPS {
+            if displacement == Vec2::ZERO {
+                return;
             }
+
+            let hitbox = self.hitbox();
+            let mut swept_area = hitbox;
+            swept_area.translate(displacement);
+            let tiles = self.nearby_tiles(world, &hitbox.combine_with(swept_area));
+
+            let hit = match sweep(&hitbox, displacement, &tiles) {
+                Some(hit) => hit,
+                None => {
+                    self.layout.x += displacement.x;
+                    self.layout.y += displacement.y;
+                    return;
+                }
+            };
+
+            self.layout.x += displacement.x * hit.time;
+            self.layout.y += displacement.y * hit.time;
+
+            let side = if hit.normal.y < 0. {
+                SideCollided::Top
+            } else if hit.normal.y > 0. {
+                SideCollided::Bottom
+            } else if hit.normal.x < 0. {
+                SideCollided::Left
+            } else {
+                SideCollided::Right
+            };
+            self.touch(side);
+
+            // Drop the part of the leftover movement that pushes into the surface
+            let remaining = displacement * (1. - hit.time);
+            displacement = remaining - hit.normal * remaining.dot(hit.normal);
         }
+    }
 
+    /// Pushes the character out of every tile it still overlaps along the axis of least
+    /// penetration, e.g. after turning around shifted the hitbox into a wall.
+    fn resolve_collisions(&mut self, world: &CollisionWorld) {
+        let mut hitbox = self.hitbox();
+        let mut candidates = self.nearby_tiles(world, &hitbox);
 
-        Ok(())
+        // Deepest contacts first: once the character is pushed out of the tile it stands on,
+        // slivers of the neighbouring tiles no longer overlap and can't snag it sideways
+        candidates.sort_by(|a, b| overlap_area(&hitbox, b).partial_cmp(&overlap_area(&hitbox, a)).unwrap());
+
+        for loc in candidates {
+            let (side, translation) = match min_translation(&hitbox, &loc) {
+                Some(contact) => contact,
+                None => continue
+            };
+
+            hitbox.x += translation.x;
+            hitbox.y += translation.y;
+            self.layout.x += translation.x;
+            self.layout.y += translation.y;
+
+            self.touch(side);
+        }
     }
 
-    pub fn _update(&mut self, _ctx: &mut Context) -> GameResult<()> {
-        let jumping: bool = self.animations.get(&CharacterAnimation::Jump).unwrap().borrow().performing;
-        let attacking: bool = self.animations.get(&CharacterAnimation::Attack).unwrap().borrow().performing;
+    pub fn update(&mut self, _ctx: &mut Context, dt: f32, input: &InputState, world: &mut CollisionWorld) -> GameResult<()>{
+        self.layout.prev_x = self.layout.x;
+        self.layout.prev_y = self.layout.y;
+
+        self._update(_ctx, dt, input).unwrap();
+        self.collect_events();
+
+        self.body.clear_contacts();
+        let movement = self.body.step(dt);
+        self.sweep_move(world, movement);
+
+        // Keep the hitbox inside the level
+        let level = world.level_bounds();
+        let hitbox = self.hitbox();
+        let correction = if hitbox.right() > level.right() {
+            level.right() - hitbox.right()
+        } else if hitbox.left() < level.left() {
+            level.left() - hitbox.left()
+        } else {
+            0.
+        };
+        if correction != 0. {
+            self.layout.x += correction;
+            self.body.velocity.x = 0.;
+        }
 
-        if !jumping {
-            self.state.jumping_left = false;
-            self.state.jumping_right = false;
+        self.resolve_collisions(world);
+
+        if let Some(id) = self.collider {
+            world.set_bounds(id, self.hitbox());
         }
 
-        if jumping || keyboard::is_key_pressed(_ctx, KeyCode::Space) {
-            if keyboard::is_key_pressed(_ctx, KeyCode::D) && keyboard::is_key_pressed(_ctx, KeyCode::Space) {
-                self.state.jumping_right = true;
-                self.state.is_flipped = false;
-            } else if keyboard::is_key_pressed(_ctx, KeyCode::A) && keyboard::is_key_pressed(_ctx, KeyCode::Space){
-                self.state.is_flipped = true;
-                self.state.jumping_left = true;
-            }
-            self.perform_jump(_ctx);
-        } else if attacking || keyboard::is_key_pressed(_ctx, KeyCode::F) { 
-            self.perform_attack(_ctx);
-        } else if keyboard::is_key_pressed(_ctx, KeyCode::D) {
-            self.run_right(_ctx);
-        } else if keyboard::is_key_pressed(_ctx, KeyCode::A) {
-            self.run_left(_ctx);
+        Ok(())
+    }
+
+    pub fn _update(&mut self, _ctx: &mut Context, dt: f32, input: &InputState) -> GameResult<()> {
+        // Attacking roots the character in place
+        let rooted = self.body.on_ground && self.graph.states[self.current].rooted;
+
+        let direction = if rooted {
+            0.
         } else {
-            self.idle(_ctx);
+            input.strength(Action::MoveRight) - input.strength(Action::MoveLeft)
+        };
+        self.body.run(direction, dt);
+
+        if direction > 0. {
+            self.face(false);
+        } else if direction < 0. {
+            self.face(true);
+        }
+
+        if input.is_just_pressed(Action::Jump) {
+            self.body.buffer_jump();
         }
+        if input.is_just_released(Action::Jump) {
+            self.body.cut_jump();
+        }
+
+        // While rooted the press stays buffered and fires once the state is over
+        let jumped = !rooted && self.body.try_jump();
+        let air_jumped = !jumped && input.is_just_pressed(Action::Jump) && self.body.air_jump();
+
+        let situation = Situation {
+            grounded: self.body.on_ground,
+            velocity: self.body.velocity,
+            air_jumped: air_jumped,
+            input: input
+        };
+        let progress = self.animations[self.current].timer.progress();
+        if let Some(next) = self.graph.next(self.current, progress, &situation) {
+            self.enter(next);
+        }
+
+        self.animate(dt);
         Ok(())
     }
 
 
 
-    pub fn draw(&mut self, ctx: &mut Context)  {
-        let current_anim = self.animations.get(&self.current).unwrap().borrow_mut();
-        let params = self.param(current_anim.src_x, current_anim.image_width);
+    pub fn draw(&mut self, ctx: &mut Context, alpha: f32)  {
+        let current_anim = &self.animations[self.current];
+        let params = self.param(current_anim, alpha);
         current_anim.image.draw(ctx, params).unwrap();
 
         // TODO - remove later
         // START
-        let mut char_rect = CustomRect::new(
-            self.layout.x,
-            self.layout.y,
-            self.layout.w,
-            self.layout.h
-        );
-        if self.state.is_flipped {
-            char_rect.fields.x -= self.layout.w / 4.;
-        }
+        let mut char_rect = CustomRect::from_rect(self.hitbox());
         char_rect.draw(ctx);
-
-        self.quadtree.draw_boundries(ctx, graphics::Color::BLUE);
-        
-        let data = self.quadtree.search(self.layout.x, self.layout.y);
-        if data.is_some() {
-            for loc in data.unwrap() {
-                let mut rect = CustomRect::from_rect(*loc);
-                rect.draw(ctx);
-            }
-        }
         // END
     }
 }
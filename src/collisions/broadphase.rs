@@ -0,0 +1,273 @@
+use ggez::Context;
+use ggez::graphics::{self, Rect};
+
+use glam::Vec2;
+use serde::Deserialize;
+
+use crate::collisions::{raycast, SpatialHash, SweepAndPrune};
+use crate::consts::SPATIAL_HASH_CELL_SIZE;
+use crate::quadtree::QuadTree;
+
+
+/// Handle of an object stored in a broad phase, stays valid until the object is removed.
+pub type EntryId = usize;
+
+
+pub struct Entry<T> {
+    pub bounds: Rect,
+    pub value: T
+}
+
+
+/// Slot storage shared by the broad phase backends. Freed ids are reused.
+pub struct Entries<T> {
+    slots: Vec<Option<Entry<T>>>,
+    free: Vec<EntryId>
+}
+
+impl<T> Entries<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new()
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn get(&self, id: EntryId) -> Option<&Entry<T>> {
+        self.slots.get(id).and_then(|entry| entry.as_ref())
+    }
+
+    pub fn insert(&mut self, bounds: Rect, value: T) -> EntryId {
+        let entry = Some(Entry {
+            bounds: bounds,
+            value: value
+        });
+
+        match self.free.pop() {
+            Some(id) => {
+                self.slots[id] = entry;
+                id
+            },
+            None => {
+                self.slots.push(entry);
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: EntryId) -> Option<Entry<T>> {
+        let entry = self.slots.get_mut(id)?.take()?;
+        self.free.push(id);
+        Some(entry)
+    }
+
+    /// Returns the previous bounds.
+    pub fn set_bounds(&mut self, id: EntryId, bounds: Rect) -> Option<Rect> {
+        let entry = self.slots.get_mut(id)?.as_mut()?;
+        Some(std::mem::replace(&mut entry.bounds, bounds))
+    }
+
+    #[allow(dead_code)]
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.free.clear();
+    }
+
+    #[allow(dead_code)]
+    pub fn iter(&self) -> impl Iterator<Item = (EntryId, &Entry<T>)> {
+        self.slots.iter().enumerate().filter_map(|(id, entry)| entry.as_ref().map(|entry| (id, entry)))
+    }
+}
+
+
+/// Finds the objects that might collide with an area, narrow phase tests run on the result.
+pub trait BroadPhase<T> {
+    fn insert(&mut self, bounds: Rect, value: T) -> EntryId;
+
+    fn remove(&mut self, id: EntryId) -> Option<T>;
+
+    /// Moves an object to `bounds`, keeping its id.
+    fn update(&mut self, id: EntryId, bounds: Rect) -> bool;
+
+    fn get(&self, id: EntryId) -> Option<&Entry<T>>;
+
+    #[allow(dead_code)]
+    fn len(&self) -> usize;
+
+    /// Removes every object, ids handed out so far become invalid.
+    #[allow(dead_code)]
+    fn clear(&mut self);
+
+    /// Objects whose bounds overlap or touch `area`.
+    fn query(&self, area: &Rect) -> Vec<(EntryId, &Entry<T>)>;
+
+    /// Objects hit by a ray from `origin` along the unit vector `direction`,
+    /// nearest first, each paired with the distance at which the ray enters it.
+    fn raycast(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Vec<(f32, EntryId, &Entry<T>)> {
+        let end = origin + direction * max_distance;
+        let area = Rect::new(
+            origin.x.min(end.x),
+            origin.y.min(end.y),
+            (origin.x - end.x).abs(),
+            (origin.y - end.y).abs()
+        );
+
+        let mut hits: Vec<(f32, EntryId, &Entry<T>)> = self.query(&area).into_iter()
+            .filter_map(|(id, entry)| {
+                raycast(origin, direction, max_distance, &entry.bounds).map(|distance| (distance, id, entry))
+            })
+            .collect();
+
+        hits.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        hits
+    }
+
+    fn draw_debug(&self, _ctx: &mut Context) {}
+}
+
+
+/// Broad phase backend a level is indexed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum BroadPhaseKind {
+    QuadTree,
+    // Uniform grid, cheap updates for many moving objects
+    SpatialHash,
+    // Objects sorted along x, suits wide levels with little vertical spread
+    SweepAndPrune
+}
+
+impl BroadPhaseKind {
+    #[allow(dead_code)]
+    pub const ALL: [BroadPhaseKind; 3] = [
+        BroadPhaseKind::QuadTree,
+        BroadPhaseKind::SpatialHash,
+        BroadPhaseKind::SweepAndPrune
+    ];
+
+    /// Empty index covering `bounds`. Objects outside of it are still found, only slower.
+    pub fn build<T: 'static>(&self, bounds: Rect) -> Box<dyn BroadPhase<T>> {
+        match self {
+            BroadPhaseKind::QuadTree => Box::new(QuadTree::new(bounds.x, bounds.y, bounds.w, bounds.h)),
+            BroadPhaseKind::SpatialHash => Box::new(SpatialHash::new(SPATIAL_HASH_CELL_SIZE)),
+            BroadPhaseKind::SweepAndPrune => Box::new(SweepAndPrune::new())
+        }
+    }
+}
+
+impl Default for BroadPhaseKind {
+    fn default() -> Self {
+        BroadPhaseKind::QuadTree
+    }
+}
+
+
+impl<T> BroadPhase<T> for QuadTree<T> {
+    fn insert(&mut self, bounds: Rect, value: T) -> EntryId {
+        QuadTree::insert(self, bounds, value)
+    }
+
+    fn remove(&mut self, id: EntryId) -> Option<T> {
+        QuadTree::remove(self, id)
+    }
+
+    fn update(&mut self, id: EntryId, bounds: Rect) -> bool {
+        QuadTree::update(self, id, bounds)
+    }
+
+    fn get(&self, id: EntryId) -> Option<&Entry<T>> {
+        QuadTree::get(self, id)
+    }
+
+    fn len(&self) -> usize {
+        QuadTree::len(self)
+    }
+
+    fn clear(&mut self) {
+        QuadTree::clear(self)
+    }
+
+    fn query(&self, area: &Rect) -> Vec<(EntryId, &Entry<T>)> {
+        QuadTree::query(self, area)
+    }
+
+    fn raycast(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Vec<(f32, EntryId, &Entry<T>)> {
+        QuadTree::raycast(self, origin, direction, max_distance)
+    }
+
+    fn draw_debug(&self, ctx: &mut Context) {
+        self.draw_boundries(ctx, graphics::Color::BLUE);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utils::test_rng::Rng;
+
+    fn sorted_ids<T>(hits: Vec<(EntryId, &Entry<T>)>) -> Vec<EntryId> {
+        let mut ids: Vec<EntryId> = hits.into_iter().map(|(id, _)| id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn backends_agree_with_brute_force() {
+        let level = Rect::new(0., 0., 4000., 800.);
+        for kind in BroadPhaseKind::ALL {
+            let mut rng = Rng(2024);
+            let mut index: Box<dyn BroadPhase<u32>> = kind.build(level);
+            let mut rects: Vec<(EntryId, Rect)> = Vec::new();
+
+            for step in 0..3000u32 {
+                let size = if step % 10 == 0 { 900. } else { 100. };
+                match step % 4 {
+                    0 if !rects.is_empty() => {
+                        let idx = (rng.next() * rects.len() as f32) as usize % rects.len();
+                        let (id, _) = rects.swap_remove(idx);
+                        assert!(index.remove(id).is_some(), "{:?}", kind);
+                    },
+                    1 if !rects.is_empty() => {
+                        let idx = (rng.next() * rects.len() as f32) as usize % rects.len();
+                        let bounds = rng.rect(&level, size);
+                        assert!(index.update(rects[idx].0, bounds), "{:?}", kind);
+                        rects[idx].1 = bounds;
+                    },
+                    _ => {
+                        let bounds = rng.rect(&level, size);
+                        rects.push((index.insert(bounds, step), bounds));
+                    }
+                }
+
+                let area = rng.rect(&level, 300.);
+                let mut expected: Vec<EntryId> = rects.iter()
+                    .filter(|(_, bounds)| bounds.overlaps(&area))
+                    .map(|(id, _)| *id)
+                    .collect();
+                expected.sort();
+
+                assert_eq!(sorted_ids(index.query(&area)), expected, "{:?}", kind);
+                assert_eq!(index.len(), rects.len(), "{:?}", kind);
+            }
+
+            for (id, bounds) in &rects {
+                assert_eq!(index.get(*id).unwrap().bounds, *bounds);
+
+                let origin = Vec2::new(bounds.x - 50., bounds.y + bounds.h / 2.);
+                let hits = index.raycast(origin, Vec2::new(1., 0.), 60. + bounds.w);
+                assert!(hits.iter().any(|(_, hit, _)| hit == id), "{:?}", kind);
+                assert!(hits.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+            }
+
+            index.clear();
+            assert_eq!(index.len(), 0);
+            assert!(index.query(&level).is_empty());
+        }
+    }
+}
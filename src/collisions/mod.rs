@@ -1,6 +1,21 @@
+pub mod broadphase;
+pub use broadphase::{BroadPhase, BroadPhaseKind, Entry, Entries, EntryId};
+
+pub mod spatial_hash;
+pub use spatial_hash::SpatialHash;
+
+pub mod sweep_and_prune;
+pub use sweep_and_prune::SweepAndPrune;
+
+pub mod world;
+pub use world::{ColliderId, CollisionWorld, Layer};
+
 use ggez::graphics::Rect;
 
+use glam::Vec2;
+
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SideCollided {
     Top,
     Bottom,
@@ -9,29 +24,213 @@ pub enum SideCollided {
 }
 
 
-pub fn rect_collision(r1: &Rect, r2: &Rect) -> Option<SideCollided> {
-    let dx = (r1.x + r1.w / 2.) - (r2.x + r2.w / 2.);
-    let dy = (r1.y + r1.h / 2.) - (r2.y + r2.h / 2.);
-    let w = (r1.w + r2.w) / 2.;
-    let h = (r1.h + r2.h) / 2.;
-    let cross_width = w * dy;
-    let cross_height = h * dx;
+/// Side of `r2` that `r1` overlaps, together with the smallest translation
+/// that moves `r1` out of `r2`. Rects that merely touch do not overlap.
+pub fn min_translation(r1: &Rect, r2: &Rect) -> Option<(SideCollided, Vec2)> {
+    let overlap_x = (r1.x + r1.w).min(r2.x + r2.w) - r1.x.max(r2.x);
+    let overlap_y = (r1.y + r1.h).min(r2.y + r2.h) - r1.y.max(r2.y);
 
-    if dx.abs() <= w && dy.abs() <= h {
-        if cross_width > cross_height {
-            if cross_width > -cross_height {
-                return Some(SideCollided::Bottom);
-            } else {
-                return Some(SideCollided::Left);
-            }
+    if overlap_x <= 0. || overlap_y <= 0. {
+        return None;
+    }
+
+    let r1_center = Vec2::new(r1.x + r1.w / 2., r1.y + r1.h / 2.);
+    let r2_center = Vec2::new(r2.x + r2.w / 2., r2.y + r2.h / 2.);
+
+    if overlap_x < overlap_y {
+        if r1_center.x < r2_center.x {
+            Some((SideCollided::Left, Vec2::new(-overlap_x, 0.)))
         } else {
-            if cross_width > -cross_height {
-                return Some(SideCollided::Right);
-            } else {
-                return Some(SideCollided::Top);
-            }
+            Some((SideCollided::Right, Vec2::new(overlap_x, 0.)))
         }
+    } else {
+        if r1_center.y < r2_center.y {
+            Some((SideCollided::Top, Vec2::new(0., -overlap_y)))
+        } else {
+            Some((SideCollided::Bottom, Vec2::new(0., overlap_y)))
+        }
+    }
+}
+
+
+/// Area shared by two rects, zero when they do not overlap.
+pub fn overlap_area(r1: &Rect, r2: &Rect) -> f32 {
+    let overlap_x = (r1.x + r1.w).min(r2.x + r2.w) - r1.x.max(r2.x);
+    let overlap_y = (r1.y + r1.h).min(r2.y + r2.h) - r1.y.max(r2.y);
+    overlap_x.max(0.) * overlap_y.max(0.)
+}
+
+
+/// Where along its path a moving rect first touches another one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweepHit {
+    // Fraction of the displacement travelled before contact, in [0, 1]
+    pub time: f32,
+    // Surface normal of the rect that was hit
+    pub normal: Vec2
+}
+
+
+/// Swept AABB test of `moving` travelling by `displacement` against a static `target`.
+/// Rects that start out overlapping are not reported, `min_translation` handles those.
+pub fn swept_aabb(moving: &Rect, displacement: Vec2, target: &Rect) -> Option<SweepHit> {
+    // Grow the target by the moving rect, so only the moving top left corner has to be traced
+    let min = Vec2::new(target.x - moving.w, target.y - moving.h);
+    let max = Vec2::new(target.x + target.w, target.y + target.h);
+    let origin = Vec2::new(moving.x, moving.y);
+
+    let (near_x, far_x) = slab(origin.x, displacement.x, min.x, max.x)?;
+    let (near_y, far_y) = slab(origin.y, displacement.y, min.y, max.y)?;
+
+    let near = near_x.max(near_y);
+    let far = far_x.min(far_y);
+
+    // Grazing along an edge or out of reach this tick
+    if near >= far || near > 1. || near < -SWEEP_EPSILON {
+        return None;
     }
 
-    return None;
-}
\ No newline at end of file
+    let normal = if near_x > near_y {
+        Vec2::new(-displacement.x.signum(), 0.)
+    } else {
+        Vec2::new(0., -displacement.y.signum())
+    };
+
+    Some(SweepHit {
+        time: near.max(0.),
+        normal: normal
+    })
+}
+
+
+/// Earliest hit of `moving` against any of `statics`. Ties prefer floors and
+/// ceilings, so running over a row of tiles does not catch on their seams.
+pub fn sweep(moving: &Rect, displacement: Vec2, statics: &[Rect]) -> Option<SweepHit> {
+    let mut earliest: Option<SweepHit> = None;
+    for target in statics {
+        let hit = match swept_aabb(moving, displacement, target) {
+            Some(hit) => hit,
+            None => continue
+        };
+
+        earliest = match earliest {
+            Some(current) if current.time < hit.time => Some(current),
+            Some(current) if current.time == hit.time && current.normal.y != 0. => Some(current),
+            _ => Some(hit)
+        };
+    }
+
+    earliest
+}
+
+
+/// Distance along `direction` (a unit vector) at which a ray from `origin` enters `target`,
+/// zero when it starts inside. Returns `None` on a miss or past `max_distance`.
+pub fn raycast(origin: Vec2, direction: Vec2, max_distance: f32, target: &Rect) -> Option<f32> {
+    let (near_x, far_x) = slab(origin.x, direction.x, target.x, target.x + target.w)?;
+    let (near_y, far_y) = slab(origin.y, direction.y, target.y, target.y + target.h)?;
+
+    let near = near_x.max(near_y);
+    let far = far_x.min(far_y);
+
+    if near > far || far < 0. || near > max_distance {
+        return None;
+    }
+
+    Some(near.max(0.))
+}
+
+
+const SWEEP_EPSILON: f32 = 1e-4;
+
+
+// Entry and exit times of a ray along one axis through the [min, max] slab
+fn slab(origin: f32, delta: f32, min: f32, max: f32) -> Option<(f32, f32)> {
+    if delta == 0. {
+        return if origin > min && origin < max {
+            Some((f32::NEG_INFINITY, f32::INFINITY))
+        } else {
+            None
+        };
+    }
+
+    let t1 = (min - origin) / delta;
+    let t2 = (max - origin) / delta;
+    Some((t1.min(t2), t1.max(t2)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fast_movers_do_not_tunnel() {
+        let moving = Rect::new(0., 0., 10., 10.);
+        let thin = Rect::new(50., -5., 2., 20.);
+        let displacement = Vec2::new(100., 0.);
+
+        // A discrete test at the end of the tick misses the tile entirely
+        let mut end = moving;
+        end.translate(displacement);
+        assert!(min_translation(&end, &thin).is_none());
+
+        let hit = swept_aabb(&moving, displacement, &thin).unwrap();
+        assert!((hit.time - 0.4).abs() < 1e-5);
+        assert_eq!(hit.normal, Vec2::new(-1., 0.));
+    }
+
+    #[test]
+    fn normals_face_the_mover() {
+        let target = Rect::new(0., 0., 10., 10.);
+        let cases = [
+            (Rect::new(-20., 0., 10., 10.), Vec2::new(20., 0.), Vec2::new(-1., 0.)),
+            (Rect::new(20., 0., 10., 10.), Vec2::new(-20., 0.), Vec2::new(1., 0.)),
+            (Rect::new(0., -20., 10., 10.), Vec2::new(0., 20.), Vec2::new(0., -1.)),
+            (Rect::new(0., 20., 10., 10.), Vec2::new(0., -20.), Vec2::new(0., 1.))
+        ];
+
+        for (moving, displacement, normal) in cases {
+            let hit = swept_aabb(&moving, displacement, &target).unwrap();
+            assert_eq!((hit.time, hit.normal), (0.5, normal));
+        }
+
+        // Out of reach this tick
+        assert!(swept_aabb(&Rect::new(-20., 0., 10., 10.), Vec2::new(5., 0.), &target).is_none());
+    }
+
+    #[test]
+    fn grazing_and_overlapping_are_not_hits() {
+        let tile = Rect::new(20., 0., 10., 10.);
+
+        // Sliding along the top edge of the tile
+        assert!(swept_aabb(&Rect::new(0., -10., 10., 10.), Vec2::new(50., 0.), &tile).is_none());
+        // Already inside, left to `min_translation`
+        assert!(swept_aabb(&Rect::new(25., 5., 10., 10.), Vec2::new(3., 0.), &tile).is_none());
+        assert!(sweep(&Rect::new(25., 5., 10., 10.), Vec2::new(3., 0.), &[tile]).is_none());
+    }
+
+    #[test]
+    fn seams_prefer_the_floor() {
+        let moving = Rect::new(0., 0., 10., 10.);
+        let displacement = Vec2::new(10., 20.);
+        // The floor and the side of a step are reached at the same time
+        let floor = Rect::new(0., 20., 32., 10.);
+        let step = Rect::new(15., 12., 10., 8.);
+        assert_eq!(swept_aabb(&moving, displacement, &step).unwrap().normal, Vec2::new(-1., 0.));
+
+        for statics in [[floor, step], [step, floor]] {
+            let hit = sweep(&moving, displacement, &statics).unwrap();
+            assert_eq!((hit.time, hit.normal), (0.5, Vec2::new(0., -1.)));
+        }
+    }
+
+    #[test]
+    fn min_translation_pushes_out_along_the_shallow_axis() {
+        let tile = Rect::new(0., 0., 32., 32.);
+        assert_eq!(min_translation(&Rect::new(-5., 10., 10., 10.), &tile), Some((SideCollided::Left, Vec2::new(-5., 0.))));
+        assert_eq!(min_translation(&Rect::new(10., -8., 10., 10.), &tile), Some((SideCollided::Top, Vec2::new(0., -2.))));
+        // Touching is not overlapping
+        assert!(min_translation(&Rect::new(-10., 0., 10., 10.), &tile).is_none());
+    }
+}
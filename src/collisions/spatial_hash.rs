@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use ggez::Context;
+use ggez::graphics::{self, Rect};
+
+use crate::collisions::{BroadPhase, Entry, Entries, EntryId};
+
+
+/// Uniform grid of square cells, every object is listed in each cell it touches.
+pub struct SpatialHash<T> {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<EntryId>>,
+    entries: Entries<T>
+}
+
+impl<T> SpatialHash<T> {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: cell_size,
+            cells: HashMap::new(),
+            entries: Entries::new()
+        }
+    }
+
+    // Cells covered by `bounds`, edges included
+    fn _cells(&self, bounds: &Rect) -> impl Iterator<Item = (i32, i32)> {
+        let min_x = (bounds.x / self.cell_size).floor() as i32;
+        let min_y = (bounds.y / self.cell_size).floor() as i32;
+        let max_x = ((bounds.x + bounds.w) / self.cell_size).floor() as i32;
+        let max_y = ((bounds.y + bounds.h) / self.cell_size).floor() as i32;
+
+        (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+    }
+
+    fn _file(&mut self, id: EntryId, bounds: &Rect) {
+        for cell in self._cells(bounds).collect::<Vec<_>>() {
+            self.cells.entry(cell).or_insert_with(Vec::new).push(id);
+        }
+    }
+
+    fn _unfile(&mut self, id: EntryId, bounds: &Rect) {
+        for cell in self._cells(bounds).collect::<Vec<_>>() {
+            if let Some(ids) = self.cells.get_mut(&cell) {
+                ids.retain(|other| *other != id);
+                if ids.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+}
+
+
+impl<T> BroadPhase<T> for SpatialHash<T> {
+    fn insert(&mut self, bounds: Rect, value: T) -> EntryId {
+        let id = self.entries.insert(bounds, value);
+        self._file(id, &bounds);
+        id
+    }
+
+    fn remove(&mut self, id: EntryId) -> Option<T> {
+        let entry = self.entries.remove(id)?;
+        self._unfile(id, &entry.bounds);
+        Some(entry.value)
+    }
+
+    fn update(&mut self, id: EntryId, bounds: Rect) -> bool {
+        let old = match self.entries.set_bounds(id, bounds) {
+            Some(old) => old,
+            None => return false
+        };
+
+        self._unfile(id, &old);
+        self._file(id, &bounds);
+        true
+    }
+
+    fn get(&self, id: EntryId) -> Option<&Entry<T>> {
+        self.entries.get(id)
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn clear(&mut self) {
+        self.cells.clear();
+        self.entries.clear();
+    }
+
+    fn query(&self, area: &Rect) -> Vec<(EntryId, &Entry<T>)> {
+        let mut ids: Vec<EntryId> = self._cells(area)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .collect();
+
+        // Objects spanning several cells show up once per cell
+        ids.sort_unstable();
+        ids.dedup();
+
+        ids.into_iter()
+            .map(|id| (id, self.entries.get(id).unwrap()))
+            .filter(|(_, entry)| entry.bounds.overlaps(area))
+            .collect()
+    }
+
+    fn draw_debug(&self, ctx: &mut Context) {
+        for (x, y) in self.cells.keys() {
+            let mesh = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::stroke(1.),
+                Rect::new(*x as f32 * self.cell_size, *y as f32 * self.cell_size, self.cell_size, self.cell_size),
+                graphics::Color::BLUE
+            ).unwrap();
+            graphics::draw(ctx, &mesh, graphics::DrawParam::default()).unwrap();
+        }
+    }
+}
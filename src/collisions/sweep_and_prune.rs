@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+
+use ggez::graphics::Rect;
+
+use crate::collisions::{BroadPhase, Entry, Entries, EntryId};
+
+
+/// Objects kept sorted by their left edge. A query only scans the objects
+/// whose left edge falls between the area's left edge, minus the widest object, and its right edge.
+pub struct SweepAndPrune<T> {
+    // Left edges in ascending order
+    axis: Vec<(f32, EntryId)>,
+    entries: Entries<T>,
+    // How many objects have each width, keyed by the width's bits,
+    // which sort like the widths themselves as long as they aren't negative
+    widths: BTreeMap<u32, usize>
+}
+
+impl<T> SweepAndPrune<T> {
+    pub fn new() -> Self {
+        Self {
+            axis: Vec::new(),
+            entries: Entries::new(),
+            widths: BTreeMap::new()
+        }
+    }
+
+    /// Width of the widest object.
+    fn max_w(&self) -> f32 {
+        self.widths.keys().next_back().map_or(0., |bits| f32::from_bits(*bits))
+    }
+
+    fn _file(&mut self, id: EntryId, bounds: &Rect) {
+        *self.widths.entry(width_key(bounds)).or_insert(0) += 1;
+        let idx = self.axis.partition_point(|(x, _)| *x <= bounds.x);
+        self.axis.insert(idx, (bounds.x, id));
+    }
+
+    fn _unfile(&mut self, id: EntryId, bounds: &Rect) {
+        let start = self.axis.partition_point(|(x, _)| *x < bounds.x);
+        if let Some(offset) = self.axis[start..].iter().position(|(_, other)| *other == id) {
+            self.axis.remove(start + offset);
+        }
+
+        let key = width_key(bounds);
+        if let Some(count) = self.widths.get_mut(&key) {
+            *count -= 1;
+            if *count == 0 {
+                self.widths.remove(&key);
+            }
+        }
+    }
+}
+
+fn width_key(bounds: &Rect) -> u32 {
+    // Adding zero turns -0 into 0
+    (bounds.w.max(0.) + 0.).to_bits()
+}
+
+
+impl<T> BroadPhase<T> for SweepAndPrune<T> {
+    fn insert(&mut self, bounds: Rect, value: T) -> EntryId {
+        let id = self.entries.insert(bounds, value);
+        self._file(id, &bounds);
+        id
+    }
+
+    fn remove(&mut self, id: EntryId) -> Option<T> {
+        let entry = self.entries.remove(id)?;
+        self._unfile(id, &entry.bounds);
+        Some(entry.value)
+    }
+
+    fn update(&mut self, id: EntryId, bounds: Rect) -> bool {
+        let old = match self.entries.set_bounds(id, bounds) {
+            Some(old) => old,
+            None => return false
+        };
+
+        self._unfile(id, &old);
+        self._file(id, &bounds);
+        true
+    }
+
+    fn get(&self, id: EntryId) -> Option<&Entry<T>> {
+        self.entries.get(id)
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn clear(&mut self) {
+        self.axis.clear();
+        self.entries.clear();
+        self.widths.clear();
+    }
+
+    fn query(&self, area: &Rect) -> Vec<(EntryId, &Entry<T>)> {
+        let max_w = self.max_w();
+        let start = self.axis.partition_point(|(x, _)| *x < area.x - max_w);
+        let end = self.axis.partition_point(|(x, _)| *x <= area.x + area.w);
+
+        self.axis[start..end.max(start)].iter()
+            .map(|(_, id)| (*id, self.entries.get(*id).unwrap()))
+            .filter(|(_, entry)| entry.bounds.overlaps(area))
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widest_object_shrinks_back() {
+        let mut index = SweepAndPrune::new();
+        let wide = index.insert(Rect::new(0., 0., 500., 10.), ());
+        let narrow = index.insert(Rect::new(0., 0., 20., 10.), ());
+        index.insert(Rect::new(50., 0., 20., 10.), ());
+        assert_eq!(index.max_w(), 500.);
+
+        index.remove(wide);
+        assert_eq!(index.max_w(), 20.);
+        index.update(narrow, Rect::new(0., 0., 80., 10.));
+        assert_eq!(index.max_w(), 80.);
+        index.update(narrow, Rect::new(0., 0., 10., 10.));
+        assert_eq!(index.max_w(), 20.);
+
+        index.clear();
+        assert_eq!(index.max_w(), 0.);
+    }
+}
@@ -0,0 +1,185 @@
+use std::ops::{BitAnd, BitOr};
+
+use ggez::Context;
+use ggez::graphics::Rect;
+
+use glam::Vec2;
+
+use crate::collisions::{BroadPhase, BroadPhaseKind, EntryId};
+
+
+/// Set of collision layers, combined with `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Layer(u32);
+
+impl Layer {
+    pub const NONE: Layer = Layer(0);
+    pub const PLAYER: Layer = Layer(1 << 0);
+    pub const ENEMY: Layer = Layer(1 << 1);
+    pub const TERRAIN: Layer = Layer(1 << 2);
+    pub const PICKUP: Layer = Layer(1 << 3);
+    pub const TRIGGER: Layer = Layer(1 << 4);
+    // One way terrain, only solid from above
+    pub const PLATFORM: Layer = Layer(1 << 5);
+
+    pub fn intersects(&self, other: Layer) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Layer named in level files, case insensitive. "solid" and "collision" mean terrain.
+    pub fn from_name(name: &str) -> Option<Layer> {
+        match name.to_lowercase().as_str() {
+            "player" => Some(Layer::PLAYER),
+            "enemy" => Some(Layer::ENEMY),
+            "terrain" | "solid" | "collision" => Some(Layer::TERRAIN),
+            "pickup" => Some(Layer::PICKUP),
+            "trigger" => Some(Layer::TRIGGER),
+            "platform" => Some(Layer::PLATFORM),
+            _ => None
+        }
+    }
+
+    /// Layers an object on this layer interacts with unless told otherwise.
+    pub fn default_mask(&self) -> Layer {
+        match *self {
+            Layer::PLAYER => Layer::TERRAIN | Layer::PLATFORM | Layer::ENEMY | Layer::PICKUP | Layer::TRIGGER,
+            Layer::ENEMY => Layer::TERRAIN | Layer::PLATFORM | Layer::PLAYER | Layer::TRIGGER,
+            Layer::PICKUP => Layer::PLAYER,
+            Layer::TRIGGER => Layer::PLAYER | Layer::ENEMY,
+            _ => Layer::NONE
+        }
+    }
+}
+
+impl BitOr for Layer {
+    type Output = Layer;
+
+    fn bitor(self, other: Layer) -> Layer {
+        Layer(self.0 | other.0)
+    }
+}
+
+impl BitAnd for Layer {
+    type Output = Layer;
+
+    fn bitand(self, other: Layer) -> Layer {
+        Layer(self.0 & other.0)
+    }
+}
+
+
+pub type ColliderId = EntryId;
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct Collider {
+    // Layer this collider is on
+    pub layer: Layer,
+    // Layers this collider reacts to
+    pub mask: Layer
+}
+
+
+/// Every collider of a level in one broad phase. Entities register their bounds
+/// and keep them up to date, layers and masks decide which pairs interact.
+pub struct CollisionWorld {
+    index: Box<dyn BroadPhase<Collider>>,
+    // Level area, entities are kept inside of it
+    bounds: Rect
+}
+
+impl CollisionWorld {
+    pub fn new(kind: BroadPhaseKind, bounds: Rect) -> Self {
+        Self {
+            index: kind.build(bounds),
+            bounds: bounds
+        }
+    }
+
+    pub fn level_bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    pub fn add(&mut self, bounds: Rect, layer: Layer, mask: Layer) -> ColliderId {
+        self.index.insert(bounds, Collider {
+            layer: layer,
+            mask: mask
+        })
+    }
+
+    pub fn remove(&mut self, id: ColliderId) -> Option<Collider> {
+        self.index.remove(id)
+    }
+
+    pub fn set_bounds(&mut self, id: ColliderId, bounds: Rect) -> bool {
+        self.index.update(id, bounds)
+    }
+
+    pub fn collider(&self, id: ColliderId) -> Option<Collider> {
+        self.index.get(id).map(|entry| entry.value)
+    }
+
+    /// Colliders on any of the `mask` layers overlapping or touching `area`, `except` left out.
+    pub fn query(&self, area: &Rect, mask: Layer, except: Option<ColliderId>) -> Vec<(ColliderId, Rect)> {
+        self.index.query(area).into_iter()
+            .filter(|(id, entry)| Some(*id) != except && mask.intersects(entry.value.layer))
+            .map(|(id, entry)| (id, entry.bounds))
+            .collect()
+    }
+
+    /// Colliders that `id` currently overlaps and interacts with according to its mask.
+    pub fn overlapping(&self, id: ColliderId) -> Vec<ColliderId> {
+        let entry = match self.index.get(id) {
+            Some(entry) => entry,
+            None => return Vec::new()
+        };
+
+        self.query(&entry.bounds, entry.value.mask, Some(id)).into_iter()
+            .map(|(other, _)| other)
+            .collect()
+    }
+
+    /// Nearest collider on any of the `mask` layers hit by the ray, with the distance to it.
+    #[allow(dead_code)]
+    pub fn raycast(&self, origin: Vec2, direction: Vec2, max_distance: f32, mask: Layer) -> Option<(f32, ColliderId)> {
+        self.index.raycast(origin, direction, max_distance).into_iter()
+            .find(|(_, _, entry)| mask.intersects(entry.value.layer))
+            .map(|(distance, id, _)| (distance, id))
+    }
+
+    pub fn draw_debug(&self, ctx: &mut Context) {
+        self.index.draw_debug(ctx);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_decide_which_colliders_interact() {
+        let mut world = CollisionWorld::new(BroadPhaseKind::QuadTree, Rect::new(0., 0., 1000., 1000.));
+        let player = world.add(Rect::new(100., 100., 50., 50.), Layer::PLAYER, Layer::PLAYER.default_mask());
+        let ground = world.add(Rect::new(0., 150., 1000., 50.), Layer::TERRAIN, Layer::TERRAIN.default_mask());
+        let coin = world.add(Rect::new(120., 120., 10., 10.), Layer::PICKUP, Layer::PICKUP.default_mask());
+        let enemy = world.add(Rect::new(140., 100., 50., 50.), Layer::ENEMY, Layer::ENEMY.default_mask());
+
+        let mut seen = world.overlapping(player);
+        seen.sort();
+        assert_eq!(seen, vec![ground, coin, enemy]);
+
+        assert_eq!(world.overlapping(coin), vec![player]);
+        assert!(world.overlapping(ground).is_empty());
+
+        let solids = world.query(&Rect::new(0., 0., 1000., 1000.), Layer::TERRAIN, None);
+        assert_eq!(solids.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![ground]);
+
+        world.set_bounds(player, Rect::new(500., 500., 50., 50.));
+        assert!(world.overlapping(player).is_empty());
+        assert_eq!(world.overlapping(coin), Vec::<ColliderId>::new());
+
+        let down = world.raycast(Vec2::new(10., 0.), Vec2::new(0., 1.), 500., Layer::TERRAIN);
+        assert_eq!(down, Some((150., ground)));
+    }
+}
@@ -1,14 +1,76 @@
+// Game Loop Configuration
+pub const SIMULATION_FPS: u32 = 60;
+// `None` leaves rendering uncapped
+pub const RENDER_FPS: Option<u32> = None;
+pub const VSYNC: bool = false;
+
+// Input Configuration
+pub const DEFAULT_GAMEPAD_DEADZONE: f32 = 0.2;
+
 // Character Animation Configuration
 pub const CHAR_WIDTH: f32 = 144.;
 pub const CHAR_SCALE_FACTOR: f32 = 3.;
-pub const RUN_SPEED: f32 = 15.;
-pub const JUMP_SPEED_DY: f32 = 10.;
-pub const JUMP_SPEED_DX: f32 = 15.;
+pub const ANIMATION_FRAME_TIME: f32 = 0.1;
+
+// Character Physics Configuration (pixels, seconds)
+pub const GRAVITY: f32 = 2400.;
+pub const TERMINAL_VELOCITY: f32 = 1200.;
+pub const RUN_SPEED: f32 = 300.;
+pub const RUN_ACCELERATION: f32 = 2400.;
+pub const AIR_ACCELERATION: f32 = 1400.;
+pub const GROUND_FRICTION: f32 = 3000.;
+pub const AIR_FRICTION: f32 = 400.;
+pub const JUMP_HEIGHT: f32 = 180.;
+pub const AIR_JUMPS: u8 = 1;
+pub const COYOTE_TIME: f32 = 0.1;
+pub const JUMP_BUFFER_TIME: f32 = 0.12;
+pub const JUMP_CUT: f32 = 0.5;
+// Slides along surfaces per tick before giving up on the remaining movement
+pub const MAX_SWEEP_STEPS: usize = 4;
+// How far feet may sink into a one way platform and still stand on it
+pub const PLATFORM_TOLERANCE: f32 = 1.;
+// How far past the front of the hitbox an attack reaches
+pub const ATTACK_REACH: f32 = 40.;
+
+// Spatial Index Configuration
+// Objects a quadtree node holds before it splits
+pub const QUADTREE_CAPACITY: usize = 8;
+pub const QUADTREE_MAX_DEPTH: usize = 8;
+pub const SPATIAL_HASH_CELL_SIZE: f32 = 256.;
+
+// Camera Configuration (pixels, seconds)
+pub const CAMERA_DEADZONE_WIDTH: f32 = 200.;
+pub const CAMERA_DEADZONE_HEIGHT: f32 = 160.;
+pub const CAMERA_LOOK_AHEAD: f32 = 180.;
+pub const CAMERA_LOOK_AHEAD_TIME: f32 = 0.6;
+pub const CAMERA_SMOOTHING: f32 = 6.;
+pub const CAMERA_ZOOM: f32 = 1.;
+pub const CAMERA_ZOOM_SPEED: f32 = 4.;
+pub const CAMERA_MAX_SHAKE: f32 = 20.;
+pub const CAMERA_TRAUMA_DECAY: f32 = 1.5;
+// Landings faster than this shake the screen, harder the closer they get to terminal velocity
+pub const LANDING_SHAKE_SPEED: f32 = 1000.;
+pub const LANDING_TRAUMA: f32 = 0.4;
+// Attacks that connect shake the screen
+pub const HIT_TRAUMA: f32 = 0.3;
+
+// Level Configuration
+pub const DEFAULT_LEVEL: &str = "park";
+pub const DEFAULT_TILE_SIZE: f32 = 32.;
 
 // Resource path configuration
 
 pub const PUNK_DIR: &str = "/chars/punk";
 pub const BIKER_DIR: &str = "/chars/biker";
 pub const CYBORG_DIR: &str = "/chars/cyborg";
+// Animation state graph in each character directory
+pub const CHAR_ANIMATIONS_FILE: &str = "animations.toml";
+
+pub const PARK_DAY_BACKGROUND_DIR: &str = "/tiles/park/background/day";
+pub const PARK_NIGHT_BACKGROUND_DIR: &str = "/tiles/park/background/night";
+pub const PARK_TILES_DIR: &str = "/tiles/park/tiles";
+pub const PARK_OBJECTS_DIR: &str = "/tiles/park/objects";
+pub const PARK_ANIMATED_OBJECTS_DIR: &str = "/tiles/park/animated_objects";
 
-pub const PARK_DAY_BACKGROUND_DIR: &str = "/tiles/park/background/day";
\ No newline at end of file
+pub const INPUT_BINDINGS_FILE: &str = "/config/bindings.toml";
+pub const LEVELS_DIR: &str = "/levels";
\ No newline at end of file
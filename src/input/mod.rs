@@ -0,0 +1,456 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::io::Read;
+use std::str::FromStr;
+
+use ggez::{Context, GameError, GameResult};
+use ggez::event::{Axis, Button, GamepadId, KeyCode, MouseButton};
+use ggez::filesystem;
+use ggez::input::gamepad;
+
+use serde::Deserialize;
+
+use crate::consts::{INPUT_BINDINGS_FILE, DEFAULT_GAMEPAD_DEADZONE};
+
+
+/// Logical actions the game reacts to, independent of the physical device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Attack,
+    Pause,
+    MenuUp,
+    MenuDown,
+    Confirm
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AxisDirection {
+    Positive,
+    Negative
+}
+
+
+/// Physical input that can trigger an `Action`.
+/// Written in config files as `"<Device>:<Name>"`, e.g. `"Key:Space"`, `"Mouse:Left"`,
+/// `"Pad:South"` or `"Axis:LeftStickX-"` (axis pushed past the deadzone in that direction).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Pad(Button),
+    Axis(Axis, AxisDirection)
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (device, name) = s.split_once(':')
+            .ok_or_else(|| format!("binding `{}` is missing a device prefix", s))?;
+
+        let binding = match device {
+            "Key" => key_from_name(name).map(Binding::Key),
+            "Mouse" => mouse_from_name(name).map(Binding::Mouse),
+            "Pad" => button_from_name(name).map(Binding::Pad),
+            "Axis" => axis_from_name(name).map(|(axis, direction)| Binding::Axis(axis, direction)),
+            _ => None
+        };
+
+        binding.ok_or_else(|| format!("unknown binding `{}`", s))
+    }
+}
+
+impl TryFrom<String> for Binding {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+
+pub type Bindings = HashMap<Action, Vec<Binding>>;
+
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct GamepadConfig {
+    // Axis values below this magnitude are treated as zero
+    pub deadzone: f32
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            deadzone: DEFAULT_GAMEPAD_DEADZONE
+        }
+    }
+}
+
+
+#[derive(Deserialize)]
+pub struct InputConfig {
+    #[serde(default = "default_bindings")]
+    pub bindings: Bindings,
+    #[serde(default)]
+    pub gamepad: GamepadConfig
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            bindings: default_bindings(),
+            gamepad: GamepadConfig::default()
+        }
+    }
+}
+
+
+pub fn default_bindings() -> Bindings {
+    let mut bindings = HashMap::new();
+    bindings.insert(Action::MoveLeft, vec![
+        Binding::Key(KeyCode::A), Binding::Key(KeyCode::Left),
+        Binding::Pad(Button::DPadLeft), Binding::Axis(Axis::LeftStickX, AxisDirection::Negative)
+    ]);
+    bindings.insert(Action::MoveRight, vec![
+        Binding::Key(KeyCode::D), Binding::Key(KeyCode::Right),
+        Binding::Pad(Button::DPadRight), Binding::Axis(Axis::LeftStickX, AxisDirection::Positive)
+    ]);
+    bindings.insert(Action::Jump, vec![Binding::Key(KeyCode::Space), Binding::Pad(Button::South)]);
+    bindings.insert(Action::Attack, vec![
+        Binding::Key(KeyCode::F), Binding::Mouse(MouseButton::Left), Binding::Pad(Button::West)
+    ]);
+    bindings.insert(Action::Pause, vec![Binding::Key(KeyCode::Escape), Binding::Pad(Button::Start)]);
+    bindings.insert(Action::MenuUp, vec![
+        Binding::Key(KeyCode::W), Binding::Key(KeyCode::Up),
+        Binding::Pad(Button::DPadUp), Binding::Axis(Axis::LeftStickY, AxisDirection::Positive)
+    ]);
+    bindings.insert(Action::MenuDown, vec![
+        Binding::Key(KeyCode::S), Binding::Key(KeyCode::Down),
+        Binding::Pad(Button::DPadDown), Binding::Axis(Axis::LeftStickY, AxisDirection::Negative)
+    ]);
+    bindings.insert(Action::Confirm, vec![Binding::Key(KeyCode::Return), Binding::Pad(Button::South)]);
+    bindings
+}
+
+
+/// Reads the input configuration from a TOML file in the resource directory.
+/// Actions missing from the file keep their default bindings.
+pub fn load_config(_ctx: &mut Context, path: &str) -> GameResult<InputConfig> {
+    let mut file = filesystem::open(_ctx, path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    let mut parsed: InputConfig = toml::from_str(&contents)
+        .map_err(|e| GameError::ConfigError(format!("{}: {}", path, e)))?;
+
+    let mut bindings = default_bindings();
+    bindings.extend(parsed.bindings);
+    parsed.bindings = bindings;
+    Ok(parsed)
+}
+
+
+#[derive(Default)]
+struct PadState {
+    buttons: HashSet<Button>,
+    axes: HashMap<Axis, f32>
+}
+
+
+/// Tracks physical device state fed from the event handler and resolves it
+/// into actions once per simulation tick. All connected gamepads drive the same actions.
+pub struct InputState {
+    bindings: Bindings,
+    gamepad: GamepadConfig,
+    held: HashSet<Binding>,
+    // Pressed since the last tick, so taps shorter than a tick still register
+    tapped: HashSet<Binding>,
+    // Released since the last tick, and during the tick before the current one
+    released: HashSet<Binding>,
+    just_released: HashSet<Binding>,
+    pads: HashMap<GamepadId, PadState>,
+    current: HashMap<Action, f32>,
+    previous: HashMap<Action, f32>
+}
+
+impl InputState {
+    pub fn new(_ctx: &mut Context) -> Self {
+        let config = match load_config(_ctx, INPUT_BINDINGS_FILE) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Could not load input bindings, using defaults: {}", e);
+                InputConfig::default()
+            }
+        };
+
+        Self::with_config(config)
+    }
+
+    pub fn with_config(config: InputConfig) -> Self {
+        Self {
+            bindings: config.bindings,
+            gamepad: config.gamepad,
+            held: HashSet::new(),
+            tapped: HashSet::new(),
+            released: HashSet::new(),
+            just_released: HashSet::new(),
+            pads: HashMap::new(),
+            current: HashMap::new(),
+            previous: HashMap::new()
+        }
+    }
+
+    pub fn press(&mut self, binding: Binding) {
+        self.held.insert(binding);
+        self.tapped.insert(binding);
+    }
+
+    pub fn release(&mut self, binding: Binding) {
+        self.held.remove(&binding);
+        self.released.insert(binding);
+    }
+
+    pub fn press_button(&mut self, id: GamepadId, button: Button) {
+        self.pads.entry(id).or_default().buttons.insert(button);
+        self.tapped.insert(Binding::Pad(button));
+    }
+
+    pub fn release_button(&mut self, id: GamepadId, button: Button) {
+        self.pads.entry(id).or_default().buttons.remove(&button);
+    }
+
+    pub fn move_axis(&mut self, id: GamepadId, axis: Axis, value: f32) {
+        self.pads.entry(id).or_default().axes.insert(axis, value);
+    }
+
+    /// Picks up newly connected gamepads and drops the state of disconnected ones,
+    /// so an unplugged controller can not leave an action stuck.
+    pub fn sync_gamepads(&mut self, _ctx: &mut Context) {
+        let mut connected = HashSet::new();
+        for (id, _) in gamepad::gamepads(_ctx) {
+            self.pads.entry(id).or_default();
+            connected.insert(id);
+        }
+
+        self.pads.retain(|id, _| connected.contains(id));
+    }
+
+    /// How strongly a binding is activated, from 0 (idle) to 1 (fully pressed).
+    fn binding_strength(&self, binding: &Binding) -> f32 {
+        if self.held.contains(binding) || self.tapped.contains(binding) {
+            return 1.;
+        }
+
+        match binding {
+            Binding::Pad(button) => {
+                if self.pads.values().any(|pad| pad.buttons.contains(button)) { 1. } else { 0. }
+            },
+            Binding::Axis(axis, direction) => {
+                let deadzone = self.gamepad.deadzone;
+                self.pads.values().map(|pad| {
+                    let value = pad.axes.get(axis).copied().unwrap_or(0.);
+                    let value = match direction {
+                        AxisDirection::Positive => value,
+                        AxisDirection::Negative => -value
+                    };
+                    // Rescale so the strength starts at 0 right at the deadzone edge
+                    ((value - deadzone) / (1. - deadzone)).clamp(0., 1.)
+                }).fold(0., f32::max)
+            },
+            _ => 0.
+        }
+    }
+
+    /// Resolves held bindings into actions. Call once before every simulation tick.
+    pub fn tick(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+
+        let mut current = HashMap::new();
+        for (action, bindings) in &self.bindings {
+            let strength = bindings.iter()
+                .map(|b| self.binding_strength(b))
+                .fold(0., f32::max);
+            if strength > 0. {
+                current.insert(*action, strength);
+            }
+        }
+
+        self.current = current;
+        self.tapped.clear();
+        self.just_released = std::mem::take(&mut self.released);
+    }
+
+    /// Analog value of an action, 1 for digital inputs.
+    pub fn strength(&self, action: Action) -> f32 {
+        self.current.get(&action).copied().unwrap_or(0.)
+    }
+
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.current.contains_key(&action)
+    }
+
+    /// Mouse button released since the previous tick. Buttons react to the release,
+    /// so the press can't carry over as an action into the state the click opens.
+    pub fn is_clicked(&self, button: MouseButton) -> bool {
+        self.just_released.contains(&Binding::Mouse(button))
+    }
+
+    pub fn is_just_pressed(&self, action: Action) -> bool {
+        self.current.contains_key(&action) && !self.previous.contains_key(&action)
+    }
+
+    pub fn is_just_released(&self, action: Action) -> bool {
+        !self.current.contains_key(&action) && self.previous.contains_key(&action)
+    }
+}
+
+
+fn mouse_from_name(name: &str) -> Option<MouseButton> {
+    match name {
+        "Left" => Some(MouseButton::Left),
+        "Right" => Some(MouseButton::Right),
+        "Middle" => Some(MouseButton::Middle),
+        _ => None
+    }
+}
+
+
+fn button_from_name(name: &str) -> Option<Button> {
+    let button = match name {
+        "South" => Button::South,
+        "East" => Button::East,
+        "North" => Button::North,
+        "West" => Button::West,
+        "LeftTrigger" => Button::LeftTrigger,
+        "LeftTrigger2" => Button::LeftTrigger2,
+        "RightTrigger" => Button::RightTrigger,
+        "RightTrigger2" => Button::RightTrigger2,
+        "Select" => Button::Select,
+        "Start" => Button::Start,
+        "Mode" => Button::Mode,
+        "LeftThumb" => Button::LeftThumb,
+        "RightThumb" => Button::RightThumb,
+        "DPadUp" => Button::DPadUp,
+        "DPadDown" => Button::DPadDown,
+        "DPadLeft" => Button::DPadLeft,
+        "DPadRight" => Button::DPadRight,
+        _ => return None
+    };
+
+    Some(button)
+}
+
+
+fn axis_from_name(name: &str) -> Option<(Axis, AxisDirection)> {
+    let direction = if name.ends_with('+') {
+        AxisDirection::Positive
+    } else if name.ends_with('-') {
+        AxisDirection::Negative
+    } else {
+        return None;
+    };
+
+    let axis = match &name[..name.len() - 1] {
+        "LeftStickX" => Axis::LeftStickX,
+        "LeftStickY" => Axis::LeftStickY,
+        "RightStickX" => Axis::RightStickX,
+        "RightStickY" => Axis::RightStickY,
+        "LeftZ" => Axis::LeftZ,
+        "RightZ" => Axis::RightZ,
+        "DPadX" => Axis::DPadX,
+        "DPadY" => Axis::DPadY,
+        _ => return None
+    };
+
+    Some((axis, direction))
+}
+
+
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    let key = match name {
+        "A" => KeyCode::A,
+        "B" => KeyCode::B,
+        "C" => KeyCode::C,
+        "D" => KeyCode::D,
+        "E" => KeyCode::E,
+        "F" => KeyCode::F,
+        "G" => KeyCode::G,
+        "H" => KeyCode::H,
+        "I" => KeyCode::I,
+        "J" => KeyCode::J,
+        "K" => KeyCode::K,
+        "L" => KeyCode::L,
+        "M" => KeyCode::M,
+        "N" => KeyCode::N,
+        "O" => KeyCode::O,
+        "P" => KeyCode::P,
+        "Q" => KeyCode::Q,
+        "R" => KeyCode::R,
+        "S" => KeyCode::S,
+        "T" => KeyCode::T,
+        "U" => KeyCode::U,
+        "V" => KeyCode::V,
+        "W" => KeyCode::W,
+        "X" => KeyCode::X,
+        "Y" => KeyCode::Y,
+        "Z" => KeyCode::Z,
+        "Key0" => KeyCode::Key0,
+        "Key1" => KeyCode::Key1,
+        "Key2" => KeyCode::Key2,
+        "Key3" => KeyCode::Key3,
+        "Key4" => KeyCode::Key4,
+        "Key5" => KeyCode::Key5,
+        "Key6" => KeyCode::Key6,
+        "Key7" => KeyCode::Key7,
+        "Key8" => KeyCode::Key8,
+        "Key9" => KeyCode::Key9,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Space" => KeyCode::Space,
+        "Return" => KeyCode::Return,
+        "Escape" => KeyCode::Escape,
+        "Tab" => KeyCode::Tab,
+        "Back" => KeyCode::Back,
+        "LShift" => KeyCode::LShift,
+        "RShift" => KeyCode::RShift,
+        "LControl" => KeyCode::LControl,
+        "RControl" => KeyCode::RControl,
+        "LAlt" => KeyCode::LAlt,
+        "RAlt" => KeyCode::RAlt,
+        _ => return None
+    };
+
+    Some(key)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clicks_fire_on_release() {
+        let mut input = InputState::with_config(InputConfig::default());
+        input.press(Binding::Mouse(MouseButton::Left));
+        input.tick();
+        assert!(input.is_pressed(Action::Attack));
+        assert!(!input.is_clicked(MouseButton::Left));
+
+        // Whatever the click opens no longer sees the button held
+        input.release(Binding::Mouse(MouseButton::Left));
+        input.tick();
+        assert!(input.is_clicked(MouseButton::Left));
+        assert!(!input.is_pressed(Action::Attack));
+
+        input.tick();
+        assert!(!input.is_clicked(MouseButton::Left));
+    }
+}
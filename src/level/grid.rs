@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::consts::{DEFAULT_TILE_SIZE, PARK_TILES_DIR};
+
+
+/// Level geometry written straight into the level file, one character per tile.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TileGrid {
+    // Width and height of a cell in pixels
+    pub tile_size: f32,
+    // Directory the legend's images are in
+    pub tileset: String,
+    pub legend: BTreeMap<char, GridTile>,
+    // Top row first, '.' and ' ' are empty cells
+    pub rows: Vec<String>
+}
+
+impl Default for TileGrid {
+    fn default() -> Self {
+        Self {
+            tile_size: DEFAULT_TILE_SIZE,
+            tileset: PARK_TILES_DIR.to_string(),
+            legend: BTreeMap::new(),
+            rows: Vec::new()
+        }
+    }
+}
+
+impl TileGrid {
+    /// Cells across, the longest row decides.
+    pub fn width(&self) -> u32 {
+        self.rows.iter().map(|row| row.chars().count()).max().unwrap_or(0) as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.rows.len() as u32
+    }
+}
+
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GridTile {
+    // Relative to the grid's tileset
+    pub image: String,
+    #[serde(default = "default_solid")]
+    pub solid: bool
+}
+
+fn default_solid() -> bool {
+    true
+}
@@ -0,0 +1,207 @@
+use std::io::Read;
+
+use ggez::{Context, GameError, GameResult};
+use ggez::filesystem;
+use ggez::graphics::{self, Rect};
+
+use serde::Deserialize;
+
+use crate::camera::CameraParams;
+use crate::collisions::BroadPhaseKind;
+use crate::tile::BackgroundTheme;
+use crate::consts::LEVELS_DIR;
+use crate::utils::resolve_path;
+
+
+pub mod grid;
+pub use grid::TileGrid;
+
+pub mod spawn;
+pub use spawn::{CharacterSpawn, ObjectSpawn, PropSpawn, TriggerSpawn};
+
+pub mod tiled;
+pub use tiled::TileMap;
+
+
+/// Everything a level is made of, read from `LEVELS_DIR/<name>.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LevelConfig {
+    pub broad_phase: BroadPhaseKind,
+    // The map's `background` property when left out, day without either
+    pub background: Option<BackgroundTheme>,
+    // Tiled map with the level geometry, relative to `LEVELS_DIR`
+    pub map: Option<String>,
+    // Geometry written in the level file, used when there is no map
+    pub tiles: Option<TileGrid>,
+    // Level size in pixels, taken from the geometry or the screen when left out
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+    pub camera: CameraParams,
+    // Used unless a character was picked in the menu
+    pub player: CharacterSpawn,
+    pub enemies: Vec<CharacterSpawn>,
+    pub props: Vec<PropSpawn>,
+    pub objects: Vec<ObjectSpawn>,
+    pub triggers: Vec<TriggerSpawn>
+}
+
+impl LevelConfig {
+    pub fn new(_ctx: &mut Context, name: &str) -> Self {
+        match load_config(_ctx, name) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Could not load level {}, using defaults: {}", name, e);
+                LevelConfig::default()
+            }
+        }
+    }
+
+    /// Tile map from the level's Tiled map or tile grid, `None` when it has neither.
+    pub fn terrain(&self, _ctx: &mut Context) -> GameResult<Option<TileMap>> {
+        if let Some(path) = &self.map {
+            return TileMap::load(_ctx, &resolve_path(LEVELS_DIR, path)).map(Some);
+        }
+
+        match &self.tiles {
+            Some(grid) => TileMap::load_grid(_ctx, grid).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// Level area, `geometry` is the size of the level's tile map if it has one.
+    pub fn bounds(&self, _ctx: &mut Context, geometry: Option<Rect>) -> Rect {
+        let fallback = geometry.unwrap_or_else(|| graphics::screen_coordinates(_ctx));
+        Rect::new(0., 0., self.width.unwrap_or(fallback.w), self.height.unwrap_or(fallback.h))
+    }
+}
+
+
+pub fn load_config(_ctx: &mut Context, name: &str) -> GameResult<LevelConfig> {
+    let path = format!("{}/{}.toml", LEVELS_DIR, name);
+    let mut file = filesystem::open(_ctx, &path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    toml::from_str(&contents).map_err(|e| GameError::ConfigError(format!("{}: {}", path, e)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::character::chars::CharacterKind;
+    use crate::animation::LoopMode;
+    use crate::tile::{Interaction, ObjectKind, PropCollision, PropLayer};
+
+    const LEVEL: &str = r##"
+        background = "night"
+
+        [tiles]
+        rows = [
+            "..o.",
+            "#==#"
+        ]
+
+        [tiles.legend]
+        "#" = { image = "Tile_01.png" }
+        "=" = { image = "Tile_02.png" }
+        "o" = { image = "Tile_40.png", solid = false }
+
+        [player]
+        character = "Biker"
+        x = 10
+        y = 64
+
+        [[enemies]]
+        character = "Cyborg"
+        x = 90
+        y = 64
+
+        [[props]]
+        image = "Benches/1.png"
+        x = 40
+        y = 64
+
+        [[props]]
+        image = "Fence/1.png"
+        x = 60
+        y = 64
+        scale = 2
+        layer = "front"
+        collision = "solid"
+
+        [[objects]]
+        kind = "money"
+        x = 20
+        y = 32
+
+        [[objects]]
+        kind = "chest"
+        x = 50
+        y = 64
+        scale = 2
+        playback = "pingpong"
+        interaction = "none"
+
+        [[triggers]]
+        x = 0
+        y = 0
+        width = 32
+        height = 64
+        once = false
+        pan = { x = 100, y = 50, duration = 2 }
+        zoom = 1.5
+    "##;
+
+    #[test]
+    fn reads_level_files() {
+        let config: LevelConfig = toml::from_str(LEVEL).unwrap();
+        assert_eq!(config.background, Some(BackgroundTheme::Night));
+        assert_eq!(config.player, CharacterSpawn { character: CharacterKind::Biker, x: 10., y: 64. });
+        assert_eq!(config.enemies[0].character, CharacterKind::Cyborg);
+        assert_eq!(config.props[0].image, "Benches/1.png");
+        assert_eq!((config.props[0].scale, config.props[0].layer, config.props[0].collision), (1., PropLayer::Back, PropCollision::None));
+        assert_eq!((config.props[1].scale, config.props[1].layer, config.props[1].collision), (2., PropLayer::Front, PropCollision::Solid));
+
+        let grid = config.tiles.unwrap();
+        assert_eq!((grid.width(), grid.height(), grid.tile_size), (4, 2, 32.));
+        assert!(grid.legend[&'#'].solid);
+        assert!(!grid.legend[&'o'].solid);
+
+        let (coin, chest) = (&config.objects[0], &config.objects[1]);
+        assert_eq!((coin.kind, coin.scale, coin.playback, coin.interaction), (ObjectKind::Money, 1., None, None));
+        assert_eq!((chest.playback, chest.interaction), (Some(LoopMode::PingPong), Some(Interaction::None)));
+        let trigger = &config.triggers[0];
+        assert!(!trigger.once);
+        assert_eq!(trigger.pan.map(|pan| pan.hold), Some(0.));
+        assert_eq!(trigger.zoom, Some(1.5));
+
+        // Everything is optional
+        let empty: LevelConfig = toml::from_str("").unwrap();
+        assert_eq!(empty.background, None);
+        assert_eq!(empty.player, CharacterSpawn::default());
+        assert!(empty.tiles.is_none() && empty.enemies.is_empty());
+    }
+
+    #[test]
+    fn shipped_levels_parse() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/levels");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map_or(false, |ext| ext == "toml") {
+                let contents = std::fs::read_to_string(&path).unwrap();
+                let config = match toml::from_str::<LevelConfig>(&contents) {
+                    Ok(config) => config,
+                    Err(e) => panic!("{}: {}", path.display(), e)
+                };
+
+                let objects = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/tiles/park/objects");
+                for prop in &config.props {
+                    assert!(std::path::Path::new(objects).join(&prop.image).exists(), "{}", prop.image);
+                }
+            }
+        }
+    }
+}
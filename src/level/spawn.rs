@@ -0,0 +1,131 @@
+use ggez::{Context, GameResult};
+use ggez::graphics::Rect;
+
+use glam::Vec2;
+use serde::Deserialize;
+
+use crate::character::chars::CharacterKind;
+use crate::animation::LoopMode;
+use crate::tile::{AnimatedObject, Interaction, ObjectKind, Prop, PropCollision, PropLayer};
+use crate::utils::join_paths;
+use crate::consts::{CHAR_WIDTH, PARK_OBJECTS_DIR};
+
+
+/// Character placed in the level. `x` and `y` are the bottom left of its hitbox, it stands on `y`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CharacterSpawn {
+    pub character: CharacterKind,
+    pub x: f32,
+    pub y: f32
+}
+
+impl Default for CharacterSpawn {
+    fn default() -> Self {
+        // Where the player started before levels had spawns
+        Self {
+            character: CharacterKind::Punk,
+            x: 100.,
+            y: 100. + CHAR_WIDTH
+        }
+    }
+}
+
+
+/// Sprite from `PARK_OBJECTS_DIR` placed with its bottom left corner at (`x`, `y`).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PropSpawn {
+    // Relative to `PARK_OBJECTS_DIR`, e.g. "Benches/1.png"
+    pub image: String,
+    pub x: f32,
+    pub y: f32,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    #[serde(default)]
+    pub layer: PropLayer,
+    #[serde(default)]
+    pub collision: PropCollision
+}
+
+impl PropSpawn {
+    pub fn build(&self, _ctx: &mut Context) -> GameResult<Prop> {
+        let path = join_paths(PARK_OBJECTS_DIR, &self.image);
+        Prop::new(_ctx, &path, Vec2::new(self.x, self.y), self.scale, self.layer, self.collision)
+    }
+}
+
+
+/// Animated object resting on `y`, its playback and interaction default to the kind's.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ObjectSpawn {
+    pub kind: ObjectKind,
+    pub x: f32,
+    pub y: f32,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    pub playback: Option<LoopMode>,
+    pub interaction: Option<Interaction>
+}
+
+impl ObjectSpawn {
+    pub fn build(&self, _ctx: &mut Context) -> AnimatedObject {
+        AnimatedObject::new(
+            _ctx,
+            self.kind,
+            Vec2::new(self.x, self.y),
+            self.scale,
+            self.playback.unwrap_or(self.kind.playback()),
+            self.interaction.unwrap_or(self.kind.interaction())
+        )
+    }
+}
+
+
+/// Area that fires when the player walks into it, `x` and `y` are its top left corner.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TriggerSpawn {
+    #[serde(default)]
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    // Fires on the first entry only, otherwise on every entry
+    #[serde(default = "default_once")]
+    pub once: bool,
+    pub pan: Option<PanSpawn>,
+    // Camera zoom while the player is inside, the level's zoom comes back on the way out
+    pub zoom: Option<f32>
+}
+
+impl TriggerSpawn {
+    pub fn bounds(&self) -> Rect {
+        Rect::new(self.x, self.y, self.width, self.height)
+    }
+}
+
+
+/// Camera pan to (`x`, `y`), e.g. to show what lies ahead.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct PanSpawn {
+    pub x: f32,
+    pub y: f32,
+    pub duration: f32,
+    #[serde(default)]
+    pub hold: f32
+}
+
+impl PanSpawn {
+    pub fn target(&self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+}
+
+
+fn default_scale() -> f32 {
+    1.
+}
+
+fn default_once() -> bool {
+    true
+}
@@ -0,0 +1,721 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use ggez::{Context, GameError, GameResult};
+use ggez::filesystem;
+use ggez::graphics::{self, Drawable, Image, Rect};
+
+use glam::Vec2;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::collisions::Layer;
+use crate::level::TileGrid;
+use crate::level::spawn::{PanSpawn, TriggerSpawn};
+use crate::tile::BackgroundTheme;
+use crate::utils::{parent_dir, resolve_path};
+
+
+// Tiled keeps flip and rotation flags in the top bits of a gid
+const GID_FLAGS: u32 = 0xE000_0000;
+
+
+/// Custom property set on a map, layer, tile or object in Tiled.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Property {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String)
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct Properties(HashMap<String, Property>);
+
+impl Properties {
+    pub fn get(&self, name: &str) -> Option<&Property> {
+        self.0.get(name)
+    }
+
+    /// Missing or non boolean properties read as false.
+    pub fn bool(&self, name: &str) -> bool {
+        matches!(self.get(name), Some(Property::Bool(true)))
+    }
+
+    pub fn float(&self, name: &str) -> Option<f32> {
+        match self.get(name)? {
+            Property::Int(value) => Some(*value as f32),
+            Property::Float(value) => Some(*value as f32),
+            _ => None
+        }
+    }
+
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            Property::String(value) => Some(value),
+            _ => None
+        }
+    }
+}
+
+
+pub struct TileLayer {
+    pub width: u32,
+    pub height: u32,
+    // Row major gids, 0 is an empty cell
+    pub tiles: Vec<u32>,
+    pub visible: bool,
+    pub properties: Properties
+}
+
+impl TileLayer {
+    pub fn gid(&self, x: u32, y: u32) -> u32 {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+
+        self.tiles.get((y * self.width + x) as usize).copied().unwrap_or(0) & !GID_FLAGS
+    }
+}
+
+
+pub struct MapObject {
+    pub name: String,
+    // Tiled's type, or class in newer versions
+    pub kind: String,
+    pub bounds: Rect,
+    pub properties: Properties
+}
+
+
+pub struct ObjectLayer {
+    pub objects: Vec<MapObject>
+}
+
+
+struct TileInfo {
+    image: String,
+    // Pixels of `image` the tile covers, the whole image when `None`
+    src: Option<Rect>,
+    properties: Properties
+}
+
+
+/// Map made in the Tiled editor and exported as JSON (tile layers in CSV format),
+/// or built from a level file's tile grid. Tile layers are drawn, tiles are solid when
+/// their layer or their tileset entry has a `solid` property set, objects go into the
+/// collision layer named by their type. Objects of type `trigger` become level triggers.
+pub struct TileMap {
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: f32,
+    pub tile_height: f32,
+    pub properties: Properties,
+    pub tile_layers: Vec<TileLayer>,
+    pub object_layers: Vec<ObjectLayer>,
+    tiles: HashMap<u32, TileInfo>,
+    images: HashMap<String, Image>
+}
+
+impl TileMap {
+    pub fn load(_ctx: &mut Context, path: &str) -> GameResult<TileMap> {
+        let contents = read_file(_ctx, path)?;
+        let mut map = TileMap::from_json(&contents, parent_dir(path), |source| read_file(_ctx, source))?;
+        map.load_images(_ctx)?;
+        Ok(map)
+    }
+
+    /// Builds the map without loading any image. `read` fetches external tilesets,
+    /// paths in the map are relative to `dir`.
+    pub fn from_json<F>(contents: &str, dir: &str, mut read: F) -> GameResult<TileMap>
+    where
+        F: FnMut(&str) -> GameResult<String>
+    {
+        let raw: RawMap = serde_json::from_str(contents).map_err(|e| GameError::ResourceLoadError(e.to_string()))?;
+
+        let mut tiles = HashMap::new();
+        for tileset in raw.tilesets {
+            let (data, base) = match &tileset.source {
+                Some(source) => {
+                    let path = resolve_path(dir, source);
+                    let contents = read(&path)?;
+                    let data: RawTileset = serde_json::from_str(&contents)
+                        .map_err(|e| GameError::ResourceLoadError(format!("{}: {}", path, e)))?;
+                    (data, parent_dir(&path).to_string())
+                },
+                None => (tileset.data, dir.to_string())
+            };
+
+            add_tileset(&mut tiles, tileset.firstgid, &data, &base);
+        }
+
+        let mut tile_layers = Vec::new();
+        let mut object_layers = Vec::new();
+        flatten_layers(raw.layers, &mut tile_layers, &mut object_layers);
+
+        Ok(TileMap {
+            width: raw.width,
+            height: raw.height,
+            tile_width: raw.tilewidth as f32,
+            tile_height: raw.tileheight as f32,
+            properties: to_properties(raw.properties),
+            tile_layers: tile_layers,
+            object_layers: object_layers,
+            tiles: tiles,
+            images: HashMap::new()
+        })
+    }
+
+    pub fn load_grid(_ctx: &mut Context, grid: &TileGrid) -> GameResult<TileMap> {
+        let mut map = TileMap::from_grid(grid)?;
+        map.load_images(_ctx)?;
+        Ok(map)
+    }
+
+    /// Builds a single layer map from a tile grid without loading any image.
+    pub fn from_grid(grid: &TileGrid) -> GameResult<TileMap> {
+        let mut gids = HashMap::new();
+        let mut tiles = HashMap::new();
+        for (idx, (symbol, tile)) in grid.legend.iter().enumerate() {
+            let gid = idx as u32 + 1;
+            let mut properties = HashMap::new();
+            properties.insert("solid".to_string(), Property::Bool(tile.solid));
+
+            tiles.insert(gid, TileInfo {
+                image: resolve_path(&grid.tileset, &tile.image),
+                src: None,
+                properties: Properties(properties)
+            });
+            gids.insert(*symbol, gid);
+        }
+
+        let (width, height) = (grid.width(), grid.height());
+        let mut data = vec![0; (width * height) as usize];
+        for (y, row) in grid.rows.iter().enumerate() {
+            for (x, symbol) in row.chars().enumerate() {
+                if symbol == '.' || symbol == ' ' {
+                    continue;
+                }
+
+                let gid = gids.get(&symbol).copied().ok_or_else(|| {
+                    GameError::ResourceLoadError(format!("Tile '{}' in row {} is not in the legend", symbol, y + 1))
+                })?;
+                data[y * width as usize + x] = gid;
+            }
+        }
+
+        Ok(TileMap {
+            width: width,
+            height: height,
+            tile_width: grid.tile_size,
+            tile_height: grid.tile_size,
+            properties: Properties::default(),
+            tile_layers: vec![TileLayer {
+                width: width,
+                height: height,
+                tiles: data,
+                visible: true,
+                properties: Properties::default()
+            }],
+            object_layers: Vec::new(),
+            tiles: tiles,
+            images: HashMap::new()
+        })
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0., 0., self.width as f32 * self.tile_width, self.height as f32 * self.tile_height)
+    }
+
+    pub fn is_solid(&self, layer: &TileLayer, gid: u32) -> bool {
+        if gid == 0 {
+            return false;
+        }
+
+        layer.properties.bool("solid") || self.tiles.get(&gid).map_or(false, |tile| tile.properties.bool("solid"))
+    }
+
+    /// Solid tiles, each horizontal run merged into a single rect.
+    pub fn solid_rects(&self) -> Vec<Rect> {
+        let mut rects = Vec::new();
+        for layer in &self.tile_layers {
+            for y in 0..layer.height {
+                let mut run: Option<u32> = None;
+                for x in 0..=layer.width {
+                    let solid = x < layer.width && self.is_solid(layer, layer.gid(x, y));
+                    match (run, solid) {
+                        (None, true) => run = Some(x),
+                        (Some(start), false) => {
+                            rects.push(Rect::new(
+                                start as f32 * self.tile_width,
+                                y as f32 * self.tile_height,
+                                (x - start) as f32 * self.tile_width,
+                                self.tile_height
+                            ));
+                            run = None;
+                        },
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        rects
+    }
+
+    /// Everything the collision world needs from the map: solid tiles as terrain,
+    /// plus objects of type terrain or platform. Other object types are left out,
+    /// triggers come from `triggers` and the rest from the level file.
+    pub fn colliders(&self) -> Vec<(Rect, Layer)> {
+        let mut colliders: Vec<(Rect, Layer)> = self.solid_rects().into_iter()
+            .map(|rect| (rect, Layer::TERRAIN))
+            .collect();
+
+        for object in self.objects() {
+            match Layer::from_name(&object.kind) {
+                Some(collision) if collision == Layer::TERRAIN || collision == Layer::PLATFORM => {
+                    colliders.push((object.bounds, collision))
+                },
+                _ => {}
+            }
+        }
+
+        colliders
+    }
+
+    /// Objects of type `trigger`. The `once` property defaults to true, `pan_x` and `pan_y`
+    /// pan the camera there, for `pan_duration` (1 second by default) and `pan_hold` seconds,
+    /// `zoom` zooms the camera while the player is inside.
+    pub fn triggers(&self) -> Vec<TriggerSpawn> {
+        self.objects().filter(|object| Layer::from_name(&object.kind) == Some(Layer::TRIGGER)).map(|object| {
+            let properties = &object.properties;
+            let pan = match (properties.float("pan_x"), properties.float("pan_y")) {
+                (Some(x), Some(y)) => Some(PanSpawn {
+                    x: x,
+                    y: y,
+                    duration: properties.float("pan_duration").unwrap_or(1.),
+                    hold: properties.float("pan_hold").unwrap_or(0.)
+                }),
+                _ => None
+            };
+
+            TriggerSpawn {
+                name: object.name.clone(),
+                x: object.bounds.x,
+                y: object.bounds.y,
+                width: object.bounds.w,
+                height: object.bounds.h,
+                once: properties.get("once").map_or(true, |_| properties.bool("once")),
+                pan: pan,
+                zoom: properties.float("zoom")
+            }
+        }).collect()
+    }
+
+    /// Time of day set by the map's `background` property.
+    pub fn background(&self) -> Option<BackgroundTheme> {
+        self.properties.string("background").and_then(BackgroundTheme::from_name)
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = &MapObject> {
+        self.object_layers.iter().flat_map(|layer| layer.objects.iter())
+    }
+
+    /// Draws the visible tile layers that fall inside `view`, in world space.
+    pub fn draw(&self, _ctx: &mut Context, view: Rect) {
+        let first_x = (view.x / self.tile_width).floor().max(0.) as u32;
+        let first_y = (view.y / self.tile_height).floor().max(0.) as u32;
+        let last_x = ((view.x + view.w) / self.tile_width).ceil().max(0.) as u32;
+        // Tiles taller than the grid stick out above their cell
+        let last_y = ((view.y + view.h) / self.tile_height).ceil().max(0.) as u32 + 1;
+
+        for layer in self.tile_layers.iter().filter(|layer| layer.visible) {
+            for y in first_y..last_y.min(layer.height) {
+                for x in first_x..last_x.min(layer.width) {
+                    self._draw_tile(_ctx, layer.gid(x, y), x, y);
+                }
+            }
+        }
+    }
+
+    fn _draw_tile(&self, _ctx: &mut Context, gid: u32, x: u32, y: u32) {
+        let tile = match self.tiles.get(&gid) {
+            Some(tile) => tile,
+            None => return
+        };
+
+        let image = match self.images.get(&tile.image) {
+            Some(image) => image,
+            None => return
+        };
+
+        let (image_w, image_h) = (image.width() as f32, image.height() as f32);
+        let src = tile.src.unwrap_or(Rect::new(0., 0., image_w, image_h));
+
+        // Tiled anchors tiles at the bottom left of their cell
+        let params = graphics::DrawParam::default()
+            .src(Rect::new(src.x / image_w, src.y / image_h, src.w / image_w, src.h / image_h))
+            .dest(Vec2::new(
+                x as f32 * self.tile_width,
+                (y + 1) as f32 * self.tile_height - src.h
+            ));
+        image.draw(_ctx, params).unwrap();
+    }
+
+    fn load_images(&mut self, _ctx: &mut Context) -> GameResult<()> {
+        for tile in self.tiles.values() {
+            if !self.images.contains_key(&tile.image) {
+                self.images.insert(tile.image.clone(), Image::new(_ctx, &tile.image)?);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+fn read_file(_ctx: &mut Context, path: &str) -> GameResult<String> {
+    let mut file = filesystem::open(_ctx, path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+
+fn add_tileset(tiles: &mut HashMap<u32, TileInfo>, firstgid: u32, data: &RawTileset, dir: &str) {
+    let mut tile_properties: HashMap<u32, Properties> = data.tiles.iter()
+        .map(|tile| (tile.id, to_properties(tile.properties.clone())))
+        .collect();
+
+    // One image cut into a grid
+    if let Some(image) = &data.image {
+        let image = resolve_path(dir, image);
+        let columns = data.columns.max(1);
+        for id in 0..data.tilecount {
+            let (column, row) = (id % columns, id / columns);
+            tiles.insert(firstgid + id, TileInfo {
+                image: image.clone(),
+                src: Some(Rect::new(
+                    (data.margin + column * (data.tilewidth + data.spacing)) as f32,
+                    (data.margin + row * (data.tileheight + data.spacing)) as f32,
+                    data.tilewidth as f32,
+                    data.tileheight as f32
+                )),
+                properties: tile_properties.remove(&id).unwrap_or_default()
+            });
+        }
+    }
+
+    // Collection of images, one per tile
+    for tile in &data.tiles {
+        if let Some(image) = &tile.image {
+            tiles.insert(firstgid + tile.id, TileInfo {
+                image: resolve_path(dir, image),
+                src: None,
+                properties: to_properties(tile.properties.clone())
+            });
+        }
+    }
+}
+
+
+// Group layers only matter in the editor, their children are used as top level layers
+fn flatten_layers(layers: Vec<RawLayer>, tile_layers: &mut Vec<TileLayer>, object_layers: &mut Vec<ObjectLayer>) {
+    for layer in layers {
+        match layer {
+            RawLayer::Tiles { width, height, data, visible, properties } => {
+                tile_layers.push(TileLayer {
+                    width: width,
+                    height: height,
+                    tiles: data,
+                    visible: visible,
+                    properties: to_properties(properties)
+                });
+            },
+            RawLayer::Objects { objects } => {
+                object_layers.push(ObjectLayer {
+                    objects: objects.into_iter().map(to_object).collect()
+                });
+            },
+            RawLayer::Group { layers } => flatten_layers(layers, tile_layers, object_layers),
+            RawLayer::Other => {}
+        }
+    }
+}
+
+
+fn to_object(raw: RawObject) -> MapObject {
+    // Tile objects are anchored at their bottom left corner
+    let y = if raw.gid.is_some() { raw.y - raw.height } else { raw.y };
+    let kind = if raw.kind.is_empty() { raw.class } else { raw.kind };
+
+    MapObject {
+        name: raw.name,
+        kind: kind,
+        bounds: Rect::new(raw.x, y, raw.width, raw.height),
+        properties: to_properties(raw.properties)
+    }
+}
+
+
+fn to_properties(raw: Vec<RawProperty>) -> Properties {
+    let properties = raw.into_iter().filter_map(|property| {
+        let value = match property.value {
+            Value::Bool(value) => Property::Bool(value),
+            Value::Number(number) if number.is_i64() => Property::Int(number.as_i64().unwrap()),
+            Value::Number(number) => Property::Float(number.as_f64().unwrap_or(0.)),
+            Value::String(value) => Property::String(value),
+            _ => return None
+        };
+        Some((property.name, value))
+    }).collect();
+
+    Properties(properties)
+}
+
+
+#[derive(Deserialize)]
+struct RawMap {
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    layers: Vec<RawLayer>,
+    #[serde(default)]
+    tilesets: Vec<RawTilesetRef>,
+    #[serde(default)]
+    properties: Vec<RawProperty>
+}
+
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum RawLayer {
+    #[serde(rename = "tilelayer")]
+    Tiles {
+        width: u32,
+        height: u32,
+        #[serde(default)]
+        data: Vec<u32>,
+        #[serde(default = "default_visible")]
+        visible: bool,
+        #[serde(default)]
+        properties: Vec<RawProperty>
+    },
+    #[serde(rename = "objectgroup")]
+    Objects {
+        #[serde(default)]
+        objects: Vec<RawObject>
+    },
+    #[serde(rename = "group")]
+    Group {
+        #[serde(default)]
+        layers: Vec<RawLayer>
+    },
+    // Image layers and anything newer
+    #[serde(other)]
+    Other
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+
+#[derive(Deserialize)]
+struct RawTilesetRef {
+    firstgid: u32,
+    // External tileset file, relative to the map
+    source: Option<String>,
+    #[serde(flatten)]
+    data: RawTileset
+}
+
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawTileset {
+    image: Option<String>,
+    tilewidth: u32,
+    tileheight: u32,
+    columns: u32,
+    tilecount: u32,
+    margin: u32,
+    spacing: u32,
+    tiles: Vec<RawTile>
+}
+
+
+#[derive(Deserialize)]
+struct RawTile {
+    id: u32,
+    image: Option<String>,
+    #[serde(default)]
+    properties: Vec<RawProperty>
+}
+
+
+#[derive(Deserialize)]
+struct RawObject {
+    #[serde(default)]
+    name: String,
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    class: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    gid: Option<u32>,
+    #[serde(default)]
+    properties: Vec<RawProperty>
+}
+
+
+#[derive(Deserialize, Clone)]
+struct RawProperty {
+    name: String,
+    value: Value
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = r#"{
+        "width": 4, "height": 3, "tilewidth": 32, "tileheight": 32,
+        "properties": [{"name": "background", "type": "string", "value": "day"}],
+        "tilesets": [
+            {"firstgid": 1, "source": "park.tsj"},
+            {"firstgid": 10, "image": "sheet.png", "tilewidth": 16, "tileheight": 16,
+             "columns": 2, "tilecount": 4, "margin": 1, "spacing": 2,
+             "tiles": [{"id": 3, "properties": [{"name": "solid", "type": "bool", "value": true}]}]}
+        ],
+        "layers": [
+            {"type": "tilelayer", "name": "ground", "width": 4, "height": 3,
+             "properties": [{"name": "solid", "type": "bool", "value": true}],
+             "data": [0, 0, 0, 0,  0, 0, 0, 0,  1, 2, 0, 2]},
+            {"type": "group", "layers": [
+                {"type": "tilelayer", "name": "decor", "width": 4, "height": 3, "visible": false,
+                 "data": [2, 0, 0, 13,  12, 0, 0, 0,  0, 0, 0, 0]}
+            ]},
+            {"type": "objectgroup", "name": "objects", "objects": [
+                {"id": 7, "name": "intro", "type": "trigger", "x": 10, "y": 20, "width": 30, "height": 40,
+                 "properties": [{"name": "pan_x", "type": "float", "value": 1.5}, {"name": "pan_y", "type": "int", "value": 2},
+                                {"name": "once", "type": "bool", "value": false}]},
+                {"id": 9, "name": "coin", "type": "pickup", "x": 0, "y": 0, "width": 8, "height": 8},
+                {"id": 8, "name": "bench", "class": "prop", "gid": 1, "x": 64, "y": 96, "width": 32, "height": 32},
+                {"id": 10, "name": "ledge", "type": "platform", "x": 32, "y": 32, "width": 64, "height": 4},
+                {"id": 11, "name": "guard", "type": "enemy", "x": 40, "y": 0, "width": 16, "height": 32}
+            ]},
+            {"type": "imagelayer", "name": "sky", "image": "sky.png"}
+        ]
+    }"#;
+
+    const TILESET: &str = r#"{
+        "tilewidth": 32, "tileheight": 32,
+        "tiles": [
+            {"id": 0, "image": "../tiles/park/tiles/Tile_01.png"},
+            {"id": 1, "image": "../tiles/park/tiles/Tile_02.png",
+             "properties": [{"name": "friction", "type": "int", "value": 3}]}
+        ]
+    }"#;
+
+    fn load() -> TileMap {
+        TileMap::from_json(MAP, "/levels", |path| {
+            assert_eq!(path, "/levels/park.tsj");
+            Ok(TILESET.to_string())
+        }).unwrap()
+    }
+
+    #[test]
+    fn reads_layers_objects_and_properties() {
+        let map = load();
+        assert_eq!(map.bounds(), Rect::new(0., 0., 128., 96.));
+        assert_eq!(map.background(), Some(BackgroundTheme::Day));
+
+        assert_eq!(map.tile_layers.len(), 2);
+        assert!(!map.tile_layers[1].visible);
+
+        let objects: Vec<&MapObject> = map.objects().collect();
+        assert_eq!(objects.len(), 5);
+        assert_eq!(objects[0].kind, "trigger");
+        assert_eq!(objects[2].kind, "prop");
+        assert_eq!(objects[2].bounds, Rect::new(64., 64., 32., 32.));
+
+        let triggers = map.triggers();
+        assert_eq!(triggers.len(), 1);
+        assert_eq!((triggers[0].name.as_str(), triggers[0].bounds(), triggers[0].once), ("intro", Rect::new(10., 20., 30., 40.), false));
+        assert_eq!(triggers[0].pan, Some(PanSpawn { x: 1.5, y: 2., duration: 1., hold: 0. }));
+        assert_eq!(triggers[0].zoom, None);
+    }
+
+    #[test]
+    fn resolves_tilesets() {
+        let map = load();
+        assert_eq!(map.tiles[&1].image, "/tiles/park/tiles/Tile_01.png");
+        assert_eq!(map.tiles[&2].properties.get("friction"), Some(&Property::Int(3)));
+
+        let cut = &map.tiles[&13];
+        assert_eq!(cut.image, "/levels/sheet.png");
+        assert_eq!(cut.src, Some(Rect::new(19., 19., 16., 16.)));
+        assert!(cut.properties.bool("solid"));
+    }
+
+    #[test]
+    fn solid_tiles_merge_into_runs_and_feed_colliders() {
+        let map = load();
+
+        // Whole ground layer is solid, in the decor layer only the tile marked solid is
+        assert_eq!(map.solid_rects(), vec![
+            Rect::new(0., 64., 64., 32.),
+            Rect::new(96., 64., 32., 32.),
+            Rect::new(96., 0., 32., 32.)
+        ]);
+
+        // Only terrain and platform objects are colliders of their own, not triggers, pickups or enemies
+        let colliders = map.colliders();
+        assert_eq!(colliders.len(), 4);
+        assert!(colliders[..3].iter().all(|(_, layer)| *layer == Layer::TERRAIN));
+        assert_eq!(colliders[3], (Rect::new(32., 32., 64., 4.), Layer::PLATFORM));
+    }
+
+    #[test]
+    fn grids_become_single_layer_maps() {
+        let mut grid = TileGrid::default();
+        grid.rows = vec!["..o".to_string(), "#=".to_string()];
+        grid.legend = toml::from_str(r##"
+            "#" = { image = "Tile_01.png" }
+            "=" = { image = "Tile_02.png" }
+            "o" = { image = "../objects/Stones/1.png", solid = false }
+        "##).unwrap();
+
+        let map = TileMap::from_grid(&grid).unwrap();
+        assert_eq!(map.bounds(), Rect::new(0., 0., 96., 64.));
+        assert_eq!(map.tile_layers[0].tiles, vec![0, 0, 3, 1, 2, 0]);
+        assert_eq!(map.tiles[&3].image, "/tiles/park/objects/Stones/1.png");
+        assert_eq!(map.solid_rects(), vec![Rect::new(0., 32., 64., 32.)]);
+
+        grid.rows.push("x".to_string());
+        assert!(TileMap::from_grid(&grid).is_err());
+    }
+
+    #[test]
+    fn shipped_maps_load() {
+        let resources = concat!(env!("CARGO_MANIFEST_DIR"), "/resources");
+        let read = |path: &str| std::fs::read_to_string(format!("{}{}", resources, path)).map_err(GameError::from);
+
+        let map = TileMap::from_json(&read("/levels/park.json").unwrap(), "/levels", read).unwrap();
+        assert!(!map.solid_rects().is_empty());
+        assert_eq!(map.triggers().len(), 1);
+        for tile in map.tiles.values() {
+            assert!(std::path::Path::new(&format!("{}{}", resources, tile.image)).exists(), "{}", tile.image);
+        }
+    }
+}
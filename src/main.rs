@@ -1,11 +1,12 @@
 use std::env;
 use std::path::{self};
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 use ggez::{Context, ContextBuilder, GameResult};
 use ggez::graphics::{self, *};
-use ggez::event::{self, EventHandler};
-use ggez::timer::check_update_time;
+use ggez::event::{self, EventHandler, KeyCode, KeyMods, MouseButton, Axis, Button, GamepadId};
+use ggez::timer::{self, check_update_time};
 
 
 mod consts;
@@ -17,13 +18,21 @@ mod collisions;
 mod quadtree;
 mod animation;
 mod character;
+mod input;
+mod physics;
+mod level;
+mod camera;
 
 
-use state::{StateMachine, State, MenuState, PlayState, AllStates};
+use state::{StateMachine, State, MenuState, PlayState, PauseState, AllStates, Payload};
+use consts::{SIMULATION_FPS, RENDER_FPS, VSYNC};
+use input::{InputState, Binding};
 
 
 fn main() {
     let mut cb = ContextBuilder::new("Rust 2d game", "Giorgi Sharmiashvili")
+        .window_setup(ggez::conf::WindowSetup::default()
+            .vsync(VSYNC))
         .window_mode(ggez::conf::WindowMode::default()
             .resizable(true)
             .maximized(true));   
@@ -45,6 +54,8 @@ fn main() {
 
 struct MyGame {
     state_machine: StateMachine,
+    input: InputState,
+    last_frame: Instant
 }
 
 impl MyGame {
@@ -53,32 +64,87 @@ impl MyGame {
 
         let menu_state = MenuState::new(_ctx);
         let play_state = PlayState::new(_ctx);
+        let pause_state = PauseState::new(_ctx);
 
         states.insert(AllStates::Menu, Box::new(menu_state) as Box<dyn State>);
         states.insert(AllStates::Play, Box::new(play_state) as Box<dyn State>);
+        states.insert(AllStates::Pause, Box::new(pause_state) as Box<dyn State>);
         
-        let state_machine = StateMachine::new(states, AllStates::Play);
+        let state_machine = StateMachine::new(_ctx, states, AllStates::Menu, Payload::Empty);
         MyGame {
             state_machine: state_machine,
+            input: InputState::new(_ctx),
+            last_frame: Instant::now()
         }
     }
 }
 
 impl EventHandler for MyGame {
     fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
-        const DESIRED_FPS: u32 = 10;
-        while check_update_time(_ctx, DESIRED_FPS) {
-            let _dt = 1. / (DESIRED_FPS as f32);
-            self.state_machine.update(_ctx);
+        self.input.sync_gamepads(_ctx);
+
+        let dt = 1. / (SIMULATION_FPS as f32);
+        while check_update_time(_ctx, SIMULATION_FPS) {
+            self.input.tick();
+            self.state_machine.update(_ctx, dt, &self.input);
+        }
+
+        if self.state_machine.is_empty() {
+            event::quit(_ctx);
         }
 
         Ok(())
     }
 
     fn draw(&mut self, _ctx: &mut Context) -> GameResult<()> {
+        // How far we are between the last simulation tick and the next one
+        let alpha = timer::remaining_update_time(_ctx).as_secs_f32() * SIMULATION_FPS as f32;
+
         graphics::clear(_ctx, Color::WHITE);
-        self.state_machine.draw(_ctx);
-        graphics::present(_ctx)
+        self.state_machine.draw(_ctx, alpha.min(1.));
+        graphics::present(_ctx)?;
+
+        if let Some(fps) = RENDER_FPS {
+            let frame_time = Duration::from_secs_f32(1. / fps as f32);
+            let elapsed = self.last_frame.elapsed();
+            if elapsed < frame_time {
+                timer::sleep(frame_time - elapsed);
+            }
+        }
+        self.last_frame = Instant::now();
+
+        Ok(())
+    }
+
+    // Overriding this also drops ggez's default quit-on-Escape, Escape is bound to `Action::Pause`
+    fn key_down_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods, repeat: bool) {
+        if !repeat {
+            self.input.press(Binding::Key(keycode));
+        }
+    }
+
+    fn key_up_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods) {
+        self.input.release(Binding::Key(keycode));
+    }
+
+    fn mouse_button_down_event(&mut self, _ctx: &mut Context, button: MouseButton, _x: f32, _y: f32) {
+        self.input.press(Binding::Mouse(button));
+    }
+
+    fn mouse_button_up_event(&mut self, _ctx: &mut Context, button: MouseButton, _x: f32, _y: f32) {
+        self.input.release(Binding::Mouse(button));
+    }
+
+    fn gamepad_button_down_event(&mut self, _ctx: &mut Context, btn: Button, id: GamepadId) {
+        self.input.press_button(id, btn);
+    }
+
+    fn gamepad_button_up_event(&mut self, _ctx: &mut Context, btn: Button, id: GamepadId) {
+        self.input.release_button(id, btn);
+    }
+
+    fn gamepad_axis_event(&mut self, _ctx: &mut Context, axis: Axis, value: f32, id: GamepadId) {
+        self.input.move_axis(id, axis, value);
     }
 
     fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
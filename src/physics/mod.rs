@@ -0,0 +1,279 @@
+use glam::Vec2;
+
+use crate::consts::{
+    GRAVITY, TERMINAL_VELOCITY, RUN_SPEED, RUN_ACCELERATION,
+    AIR_ACCELERATION, GROUND_FRICTION, AIR_FRICTION, JUMP_HEIGHT,
+    AIR_JUMPS, COYOTE_TIME, JUMP_BUFFER_TIME, JUMP_CUT
+};
+
+
+/// Tunable movement parameters, in pixels and seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct PhysicsParams {
+    pub gravity: f32,
+    pub terminal_velocity: f32,
+    pub max_run_speed: f32,
+    pub run_acceleration: f32,
+    pub air_acceleration: f32,
+    // Deceleration applied when there is no run input
+    pub ground_friction: f32,
+    pub air_friction: f32,
+    // Apex of a jump above the take-off point
+    pub jump_height: f32,
+    // Extra jumps allowed before touching the ground again
+    pub air_jumps: u8,
+    // Grace period after walking off a ledge during which a ground jump still works
+    pub coyote_time: f32,
+    // How long a jump press is remembered before landing
+    pub jump_buffer_time: f32,
+    // Upward speed is scaled by this when the jump button is released early
+    pub jump_cut: f32
+}
+
+impl PhysicsParams {
+    pub fn default() -> Self {
+        Self {
+            gravity: GRAVITY,
+            terminal_velocity: TERMINAL_VELOCITY,
+            max_run_speed: RUN_SPEED,
+            run_acceleration: RUN_ACCELERATION,
+            air_acceleration: AIR_ACCELERATION,
+            ground_friction: GROUND_FRICTION,
+            air_friction: AIR_FRICTION,
+            jump_height: JUMP_HEIGHT,
+            air_jumps: AIR_JUMPS,
+            coyote_time: COYOTE_TIME,
+            jump_buffer_time: JUMP_BUFFER_TIME,
+            jump_cut: JUMP_CUT
+        }
+    }
+}
+
+
+pub struct Body {
+    pub velocity: Vec2,
+    pub params: PhysicsParams,
+    pub on_ground: bool,
+    pub on_wall: bool,
+    pub on_ceiling: bool,
+    pub air_jumps_left: u8,
+    coyote_timer: f32,
+    jump_buffer: f32
+}
+
+impl Body {
+    pub fn new(params: PhysicsParams) -> Self {
+        Self {
+            velocity: Vec2::ZERO,
+            params: params,
+            on_ground: false,
+            on_wall: false,
+            on_ceiling: false,
+            air_jumps_left: params.air_jumps,
+            coyote_timer: 0.,
+            jump_buffer: 0.
+        }
+    }
+
+    /// Take-off speed that reaches exactly `jump_height` under `gravity`.
+    pub fn jump_velocity(&self) -> f32 {
+        (2. * self.params.gravity * self.params.jump_height).sqrt()
+    }
+
+    /// Remembers a jump press for `jump_buffer_time`.
+    pub fn buffer_jump(&mut self) {
+        self.jump_buffer = self.params.jump_buffer_time;
+    }
+
+    /// Whether a ground jump is allowed, counting the coyote time after leaving a ledge.
+    pub fn can_ground_jump(&self) -> bool {
+        self.on_ground || self.coyote_timer > 0.
+    }
+
+    /// Performs a buffered ground jump if possible.
+    pub fn try_jump(&mut self) -> bool {
+        if self.jump_buffer <= 0. || !self.can_ground_jump() {
+            return false;
+        }
+
+        self.jump();
+        true
+    }
+
+    pub fn jump(&mut self) {
+        self.velocity.y = -self.jump_velocity();
+        self.on_ground = false;
+        self.coyote_timer = 0.;
+        self.jump_buffer = 0.;
+    }
+
+    /// Jumps again mid-air if the body still has air jumps left.
+    pub fn air_jump(&mut self) -> bool {
+        if self.can_ground_jump() || self.air_jumps_left == 0 {
+            return false;
+        }
+
+        self.air_jumps_left -= 1;
+        self.velocity.y = -self.jump_velocity();
+        self.jump_buffer = 0.;
+        true
+    }
+
+    /// Cuts the ascent short, called when the jump button is released early.
+    pub fn cut_jump(&mut self) {
+        if self.velocity.y < 0. {
+            self.velocity.y *= self.params.jump_cut;
+        }
+    }
+
+    /// Forgets last tick's contacts. Whatever is still touched gets flagged again
+    /// by the collision step, so walking off a ledge drops `on_ground` and the body falls.
+    pub fn clear_contacts(&mut self) {
+        self.on_ground = false;
+        self.on_wall = false;
+        self.on_ceiling = false;
+    }
+
+    pub fn hit_wall(&mut self) {
+        self.velocity.x = 0.;
+        self.on_wall = true;
+    }
+
+    pub fn hit_ceiling(&mut self) {
+        self.velocity.y = self.velocity.y.max(0.);
+        self.on_ceiling = true;
+    }
+
+    pub fn land(&mut self) {
+        self.velocity.y = 0.;
+        self.on_ground = true;
+        self.air_jumps_left = self.params.air_jumps;
+        self.coyote_timer = self.params.coyote_time;
+    }
+
+    /// Accelerates towards `direction * max_run_speed`, `direction` being in [-1, 1].
+    /// With no direction the body slows down by ground or air friction.
+    pub fn run(&mut self, direction: f32, dt: f32) {
+        let target = direction * self.params.max_run_speed;
+        let rate = match (direction != 0., self.on_ground) {
+            (true, true) => self.params.run_acceleration,
+            (true, false) => self.params.air_acceleration,
+            (false, true) => self.params.ground_friction,
+            (false, false) => self.params.air_friction
+        };
+
+        self.velocity.x = approach(self.velocity.x, target, rate * dt);
+    }
+
+    /// Applies gravity and returns the displacement for this tick.
+    pub fn step(&mut self, dt: f32) -> Vec2 {
+        self.coyote_timer = (self.coyote_timer - dt).max(0.);
+        self.jump_buffer = (self.jump_buffer - dt).max(0.);
+
+        self.velocity.y = (self.velocity.y + self.params.gravity * dt).min(self.params.terminal_velocity);
+        self.velocity * dt
+    }
+}
+
+
+fn approach(current: f32, target: f32, max_delta: f32) -> f32 {
+    if current < target {
+        (current + max_delta).min(target)
+    } else {
+        (current - max_delta).max(target)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1. / 60.;
+
+    // One physics tick in the order the character runs it, `lands` standing in for the collision step
+    fn tick(body: &mut Body, lands: bool) {
+        body.clear_contacts();
+        body.step(DT);
+        if lands {
+            body.land();
+        }
+    }
+
+    fn falling() -> Body {
+        let mut body = Body::new(PhysicsParams::default());
+        body.velocity.y = 300.;
+        body
+    }
+
+    #[test]
+    fn buffered_jump_fires_on_landing() {
+        let mut body = falling();
+        body.buffer_jump();
+        assert!(!body.try_jump());
+
+        tick(&mut body, true);
+        assert!(body.try_jump());
+        assert_eq!(body.velocity.y, -body.jump_velocity());
+
+        // A press older than the buffer is forgotten by the time the body lands
+        let mut body = falling();
+        body.buffer_jump();
+        while body.jump_buffer > 0. {
+            tick(&mut body, false);
+        }
+        tick(&mut body, true);
+        assert!(!body.try_jump());
+    }
+
+    #[test]
+    fn coyote_time_allows_late_ground_jumps() {
+        let mut body = falling();
+        tick(&mut body, true);
+
+        // Walked off the ledge, still inside the coyote time
+        tick(&mut body, false);
+        assert!(!body.on_ground);
+        body.buffer_jump();
+        assert!(body.try_jump());
+
+        let mut body = falling();
+        tick(&mut body, true);
+        let ticks = (body.params.coyote_time / DT).ceil() as usize + 1;
+        for _ in 0..ticks {
+            tick(&mut body, false);
+        }
+        body.buffer_jump();
+        assert!(!body.try_jump());
+    }
+
+    #[test]
+    fn releasing_jump_cuts_the_ascent() {
+        let mut body = falling();
+        body.jump();
+        body.cut_jump();
+        assert_eq!(body.velocity.y, -body.jump_velocity() * body.params.jump_cut);
+
+        // Already falling, nothing to cut
+        let mut body = falling();
+        body.cut_jump();
+        assert_eq!(body.velocity.y, 300.);
+    }
+
+    #[test]
+    fn landing_restores_air_jumps() {
+        let mut body = falling();
+        tick(&mut body, true);
+        body.jump();
+        tick(&mut body, false);
+
+        for _ in 0..body.params.air_jumps {
+            assert!(body.air_jump());
+        }
+        assert_eq!(body.air_jumps_left, 0);
+        assert!(!body.air_jump());
+
+        tick(&mut body, true);
+        assert_eq!(body.air_jumps_left, body.params.air_jumps);
+    }
+}
@@ -1,210 +1,426 @@
 use ggez::Context;
-use ggez::graphics::Rect;
-use ggez::graphics::{self, *};
+use ggez::graphics::{self, Rect};
 
-use crate::consts::CHAR_WIDTH;
+use glam::Vec2;
 
+use crate::collisions::{self, Entry, Entries, EntryId};
+use crate::consts::{QUADTREE_CAPACITY, QUADTREE_MAX_DEPTH};
 
-pub struct QuadTree {
-    pub boundary: Rect,
-    pub points: Vec<Rect>,
-    pub top_left: Option<Box<QuadTree>>,
-    pub top_right: Option<Box<QuadTree>>,
-    pub bottom_left: Option<Box<QuadTree>>,
-    pub bottom_right: Option<Box<QuadTree>>
-}
 
-impl QuadTree {
+/// Spatial index of objects keyed by their bounding rect.
+/// Each object lives in the smallest node that fully contains it, objects
+/// reaching outside of the tree boundary are kept in the root.
+pub struct QuadTree<T> {
+    root: Node,
+    entries: Entries<T>
+}
 
-    const LIMIT: f32 = CHAR_WIDTH * 2.5;
+impl<T> QuadTree<T> {
 
     pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
         Self {
-            boundary: Rect {
-                x: x,
-                y: y,
-                w: width,
-                h: height
-            },
-            points: Vec::new(),
-            top_left: None,
-            top_right: None,
-            bottom_left: None,
-            bottom_right: None
+            root: Node::new(Rect::new(x, y, width, height), 0),
+            entries: Entries::new()
         }
     }
 
-    pub fn insert(&mut self, x: f32, y: f32, data: Rect) {
-        if !self._in_boundary(x, y) {
-            return;
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, id: EntryId) -> Option<&Entry<T>> {
+        self.entries.get(id)
+    }
+
+    pub fn insert(&mut self, bounds: Rect, value: T) -> EntryId {
+        let id = self.entries.insert(bounds, value);
+        self.root.insert(id, bounds);
+        id
+    }
+
+    pub fn remove(&mut self, id: EntryId) -> Option<T> {
+        let entry = self.entries.remove(id)?;
+        self.root.remove(id, &entry.bounds);
+        Some(entry.value)
+    }
+
+    /// Moves an object to `bounds`, keeping its id.
+    pub fn update(&mut self, id: EntryId, bounds: Rect) -> bool {
+        let old = match self.entries.set_bounds(id, bounds) {
+            Some(old) => old,
+            None => return false
+        };
+
+        self.root.remove(id, &old);
+        self.root.insert(id, bounds);
+        true
+    }
+
+    /// Removes every object, ids handed out so far become invalid.
+    #[allow(dead_code)]
+    pub fn clear(&mut self) {
+        self.root = Node::new(self.root.boundary, 0);
+        self.entries.clear();
+    }
+
+    /// Refiles every object from scratch. Ids are kept.
+    #[allow(dead_code)]
+    pub fn rebuild(&mut self) {
+        self.root = Node::new(self.root.boundary, 0);
+        for (id, entry) in self.entries.iter() {
+            self.root.insert(id, entry.bounds);
         }
+    }
+
+    /// Objects whose bounds overlap or touch `area`.
+    pub fn query(&self, area: &Rect) -> Vec<(EntryId, &Entry<T>)> {
+        let mut ids = Vec::new();
+        self.root.query(area, &mut ids);
+        ids.into_iter().map(|id| (id, self.get(id).unwrap())).collect()
+    }
+
+    /// Objects hit by a ray from `origin` along the unit vector `direction`,
+    /// nearest first, each paired with the distance at which the ray enters it.
+    pub fn raycast(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Vec<(f32, EntryId, &Entry<T>)> {
+        let mut hits = Vec::new();
+        self.root.raycast(origin, direction, max_distance, &mut hits);
+        hits.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        hits.into_iter().map(|(distance, id)| (distance, id, self.get(id).unwrap())).collect()
+    }
 
-        if self._reach_limit() {
-            self.points.push(data);
+    pub fn draw_boundries(&self, ctx: &mut Context, color: graphics::Color) {
+        self.root.draw_boundries(ctx, color);
+    }
+}
+
+
+struct Node {
+    boundary: Rect,
+    depth: usize,
+    // Objects that fit in this node but in none of its children
+    items: Vec<(EntryId, Rect)>,
+    // Top left, top right, bottom left, bottom right
+    children: Option<Box<[Node; 4]>>
+}
+
+impl Node {
+
+    fn new(boundary: Rect, depth: usize) -> Self {
+        Self {
+            boundary: boundary,
+            depth: depth,
+            items: Vec::new(),
+            children: None
+        }
+    }
+
+    fn insert(&mut self, id: EntryId, bounds: Rect) {
+        if let Some(child) = self._child_containing(&bounds) {
+            child.insert(id, bounds);
             return;
         }
 
-        if (self.boundary.x + self.boundary.w) / 2. >= x {
+        self.items.push((id, bounds));
+        if self.children.is_none() && self.items.len() > QUADTREE_CAPACITY && self.depth < QUADTREE_MAX_DEPTH {
+            self._split();
+        }
+    }
+
+    fn remove(&mut self, id: EntryId, bounds: &Rect) -> bool {
+        if let Some(idx) = self.items.iter().position(|(other, _)| *other == id) {
+            self.items.swap_remove(idx);
+            return true;
+        }
 
-            // top left tree
-            if (self.boundary.y + self.boundary.h) / 2. >= y {
-                if self.top_left.is_none() {
-                    self.top_left = Some(Box::new(QuadTree::new(
-                        self.boundary.x,
-                        self.boundary.y,
-                        (self.boundary.x + self.boundary.w) / 2.,
-                        (self.boundary.y + self.boundary.h) / 2.
-                    )));
-                }
-                
-                self.top_left.as_mut().unwrap().insert(x, y, data);
-            } 
-            // bottom left tree
-            else {
-                if self.bottom_left.is_none() {
-                    self.bottom_left = Some(Box::new(QuadTree::new(
-                        self.boundary.x,
-                        (self.boundary.y + self.boundary.h) / 2.,
-                        (self.boundary.x + self.boundary.w) / 2.,
-                        self.boundary.h
-                    )));
-                }
-                
-                self.bottom_left.as_mut().unwrap().insert(x, y, data);
+        let removed = match self._child_containing(bounds) {
+            Some(child) => child.remove(id, bounds),
+            None => false
+        };
+
+        if removed && self._collapsible() {
+            self._merge();
+        }
+
+        removed
+    }
+
+    fn query(&self, area: &Rect, ids: &mut Vec<EntryId>) {
+        for (id, bounds) in &self.items {
+            if bounds.overlaps(area) {
+                ids.push(*id);
             }
-        } else {
-            // top right tree
-            if (self.boundary.y + self.boundary.h) / 2. >= y {
-
-                if self.top_right.is_none() {
-                    self.top_right = Some(Box::new(QuadTree::new(
-                        (self.boundary.x + self.boundary.w ) / 2.,
-                        self.boundary.y,
-                        self.boundary.w,
-                        (self.boundary.y  +self.boundary.h) / 2.
-                    )));
+        }
+
+        if let Some(children) = &self.children {
+            for child in children.iter() {
+                // Children hold only objects inside their boundary
+                if child.boundary.overlaps(area) {
+                    child.query(area, ids);
                 }
+            }
+        }
+    }
 
-                self.top_right.as_mut().unwrap().insert(x, y, data);
+    fn raycast(&self, origin: Vec2, direction: Vec2, max_distance: f32, hits: &mut Vec<(f32, EntryId)>) {
+        for (id, bounds) in &self.items {
+            if let Some(distance) = collisions::raycast(origin, direction, max_distance, bounds) {
+                hits.push((distance, *id));
             }
-            // bottom right tree
-            else {
-
-                if self.bottom_right.is_none() {
-                    self.bottom_right = Some(Box::new(QuadTree::new(
-                        (self.boundary.x + self.boundary.w) / 2.,
-                        (self.boundary.y + self.boundary.h) / 2.,
-                        self.boundary.w,
-                        self.boundary.h
-                    )));
-                }
+        }
 
-                self.bottom_right.as_mut().unwrap().insert(x, y, data);
+        if let Some(children) = &self.children {
+            for child in children.iter() {
+                if collisions::raycast(origin, direction, max_distance, &child.boundary).is_some() {
+                    child.raycast(origin, direction, max_distance, hits);
+                }
             }
         }
     }
 
-    pub fn draw_boundries(&self, ctx: &mut Context, color: graphics::Color) {
-        let rect = graphics::Rect{
-            x: self.boundary.x,
-            y: self.boundary.y,
-            w: self.boundary.w,
-            h: self.boundary.h
-        };
-
+    fn draw_boundries(&self, ctx: &mut Context, color: graphics::Color) {
         let mesh = graphics::MeshBuilder::new()
         .rectangle(
-            graphics::DrawMode::stroke(3.), 
+            graphics::DrawMode::stroke(3.),
             graphics::Rect {
                 x: 0.,
                 y: 0.,
-                w: rect.w,
-                h: rect.h
+                w: self.boundary.w,
+                h: self.boundary.h
             },
             color
         ).unwrap().build(ctx).unwrap();
-        
 
         let draw_params = graphics::DrawParam::new()
             .dest(glam::Vec2::new(self.boundary.x, self.boundary.y));
 
         graphics::draw(ctx, &mesh, draw_params).unwrap();
 
-        if !self.top_left.is_none() {
-            self.top_left.as_ref().unwrap().draw_boundries(ctx, graphics::Color::YELLOW);
+        if let Some(children) = &self.children {
+            let colors = [graphics::Color::YELLOW, graphics::Color::RED, graphics::Color::GREEN, graphics::Color::MAGENTA];
+            for (child, color) in children.iter().zip(colors) {
+                child.draw_boundries(ctx, color);
+            }
         }
+    }
 
-        if !self.top_right.is_none() {
-            self.top_right.as_ref().unwrap().draw_boundries(ctx, graphics::Color::RED);
-        }
+    fn _child_containing(&mut self, bounds: &Rect) -> Option<&mut Node> {
+        self.children.as_mut()?.iter_mut().find(|child| encloses(&child.boundary, bounds))
+    }
 
-        if !self.bottom_left.is_none() {
-            self.bottom_left.as_ref().unwrap().draw_boundries(ctx, graphics::Color::GREEN);
-        }
+    fn _split(&mut self) {
+        let b = self.boundary;
+        let (half_w, half_h) = (b.w / 2., b.h / 2.);
+        let depth = self.depth + 1;
 
-        if !self.bottom_right.is_none() {
-            self.bottom_right.as_ref().unwrap().draw_boundries(ctx, graphics::Color::MAGENTA);
+        self.children = Some(Box::new([
+            Node::new(Rect::new(b.x, b.y, half_w, half_h), depth),
+            Node::new(Rect::new(b.x + half_w, b.y, b.w - half_w, half_h), depth),
+            Node::new(Rect::new(b.x, b.y + half_h, half_w, b.h - half_h), depth),
+            Node::new(Rect::new(b.x + half_w, b.y + half_h, b.w - half_w, b.h - half_h), depth)
+        ]));
+
+        for (id, bounds) in std::mem::take(&mut self.items) {
+            self.insert(id, bounds);
         }
     }
 
-    pub fn search(&self, x: f32, y: f32) -> Option<&Vec<Rect>> {
-        if !self._in_boundary(x, y) {
-            return None;
-        }
+    // Whether all children are leaves and their objects fit back into this node
+    fn _collapsible(&self) -> bool {
+        let children = match &self.children {
+            Some(children) => children,
+            None => return false
+        };
+
+        children.iter().all(|child| child.children.is_none()) &&
+        self.items.len() + children.iter().map(|child| child.items.len()).sum::<usize>() <= QUADTREE_CAPACITY
+    }
 
-        if self._reach_limit() {
-            return Some(&self.points);
+    fn _merge(&mut self) {
+        if let Some(children) = self.children.take() {
+            for mut child in *children {
+                self.items.append(&mut child.items);
+            }
         }
+    }
+}
 
-        if (self.boundary.x + self.boundary.w) / 2. >= x {
 
-            // top left
-            if (self.boundary.y + self.boundary.h) / 2. >= y {
-                if self.top_left.is_none() {
-                    return None;
-                }
+fn encloses(outer: &Rect, inner: &Rect) -> bool {
+    inner.x >= outer.x &&
+    inner.y >= outer.y &&
+    inner.x + inner.w <= outer.x + outer.w &&
+    inner.y + inner.h <= outer.y + outer.h
+}
 
-                return self.top_left.as_ref().unwrap().search(x, y);
-            } 
-            // bottom left
-            else {
-                if self.bottom_left.is_none() {
-                    return None;
-                }
 
-                return self.bottom_left.as_ref().unwrap().search(x, y);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utils::test_rng::Rng;
+
+    // Mostly small rects, some huge ones and some poking out of the tree
+    fn random_rect(rng: &mut Rng) -> Rect {
+        let size = if rng.next() < 0.1 { 600. } else { 80. };
+        rng.rect(&Rect::new(0., 0., 1100., 900.), size)
+    }
+
+    fn brute_force(rects: &[(EntryId, Rect)], area: &Rect) -> Vec<EntryId> {
+        let mut ids: Vec<EntryId> = rects.iter()
+            .filter(|(_, bounds)| bounds.overlaps(area))
+            .map(|(id, _)| *id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn query_ids(tree: &QuadTree<()>, area: &Rect) -> Vec<EntryId> {
+        let mut ids: Vec<EntryId> = tree.query(area).into_iter().map(|(id, _)| id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn every_inserted_rect_is_found_by_an_overlapping_query() {
+        for seed in 1..20 {
+            let mut rng = Rng(seed * 7919);
+            let mut tree = QuadTree::new(0., 0., 1000., 800.);
+            let mut rects = Vec::new();
+
+            for _ in 0..500 {
+                let bounds = random_rect(&mut rng);
+                rects.push((tree.insert(bounds, ()), bounds));
             }
-        } else {
 
-            // top right tree
-            if (self.boundary.y + self.boundary.h) / 2. >= y {
-                if self.top_right.is_none() {
-                    return None;
-                }
+            for (id, bounds) in &rects {
+                // The rect itself, a single point inside it, and a random rect overlapping it
+                let point = Rect::new(bounds.x + bounds.w * rng.next(), bounds.y + bounds.h * rng.next(), 0., 0.);
+                let (left, up) = (rng.range(0., 200.), rng.range(0., 200.));
+                let around = Rect::new(point.x - left, point.y - up, left + rng.range(0., 200.), up + rng.range(0., 200.));
 
-                return self.top_right.as_ref().unwrap().search(x, y);
-            } 
-            // bottom right tree
-            else {
-                if self.bottom_right.is_none() {
-                    return None;
+                for area in [*bounds, point, around] {
+                    assert!(query_ids(&tree, &area).contains(id), "{:?} missed by {:?}", bounds, area);
                 }
+            }
+        }
+    }
+
+    #[test]
+    fn queries_match_brute_force() {
+        let mut rng = Rng(42);
+        let mut tree = QuadTree::new(0., 0., 1000., 800.);
+        let mut rects = Vec::new();
+
+        for _ in 0..1000 {
+            let bounds = random_rect(&mut rng);
+            rects.push((tree.insert(bounds, ()), bounds));
+        }
+
+        for _ in 0..500 {
+            let area = random_rect(&mut rng);
+            assert_eq!(query_ids(&tree, &area), brute_force(&rects, &area));
+        }
+    }
 
-                return self.bottom_right.as_ref().unwrap().search(x, y);
+    #[test]
+    fn removals_and_updates_keep_queries_exact() {
+        let mut rng = Rng(1234);
+        let mut tree = QuadTree::new(0., 0., 1000., 800.);
+        let mut rects = Vec::new();
+
+        for _ in 0..400 {
+            let bounds = random_rect(&mut rng);
+            rects.push((tree.insert(bounds, ()), bounds));
+        }
+
+        for step in 0..2000 {
+            let idx = (rng.next() * rects.len() as f32) as usize % rects.len();
+            match step % 3 {
+                0 => {
+                    let (id, _) = rects.swap_remove(idx);
+                    assert!(tree.remove(id).is_some());
+                    assert!(tree.remove(id).is_none());
+                },
+                1 => {
+                    let bounds = random_rect(&mut rng);
+                    assert!(tree.update(rects[idx].0, bounds));
+                    rects[idx].1 = bounds;
+                },
+                _ => {
+                    let bounds = random_rect(&mut rng);
+                    rects.push((tree.insert(bounds, ()), bounds));
+                }
             }
+
+            let area = random_rect(&mut rng);
+            assert_eq!(query_ids(&tree, &area), brute_force(&rects, &area));
+            assert_eq!(tree.len(), rects.len());
+        }
+
+        tree.rebuild();
+        for (id, bounds) in &rects {
+            assert!(query_ids(&tree, bounds).contains(id));
         }
 
+        tree.clear();
+        assert_eq!(tree.len(), 0);
+        assert!(tree.query(&Rect::new(-100., -100., 1300., 1100.)).is_empty());
     }
 
-    fn _reach_limit(&self) -> bool {
-        (self.boundary.x - self.boundary.w).abs() <= QuadTree::LIMIT * 1.5 &&
-        (self.boundary.y - self.boundary.y).abs() <= QuadTree::LIMIT * 1.5
+    #[test]
+    fn raycast_matches_brute_force_and_is_sorted() {
+        let mut rng = Rng(99);
+        let mut tree = QuadTree::new(0., 0., 1000., 800.);
+        let mut rects = Vec::new();
+
+        for _ in 0..500 {
+            let bounds = random_rect(&mut rng);
+            rects.push((tree.insert(bounds, ()), bounds));
+        }
+
+        for _ in 0..200 {
+            let origin = Vec2::new(rng.range(-100., 1100.), rng.range(-100., 900.));
+            let angle = rng.range(0., std::f32::consts::TAU);
+            let direction = Vec2::new(angle.cos(), angle.sin());
+            let max_distance = rng.range(0., 1500.);
+
+            let hits = tree.raycast(origin, direction, max_distance);
+            let mut found: Vec<EntryId> = hits.iter().map(|(_, id, _)| *id).collect();
+            found.sort();
+
+            let mut expected: Vec<EntryId> = rects.iter()
+                .filter(|(_, bounds)| collisions::raycast(origin, direction, max_distance, bounds).is_some())
+                .map(|(id, _)| *id)
+                .collect();
+            expected.sort();
+
+            assert_eq!(found, expected);
+            assert!(hits.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        }
     }
 
-    fn _in_boundary(&self, x: f32, y: f32) -> bool {
-        x >= self.boundary.x &&
-        x <= self.boundary.w &&
-        y >= self.boundary.y && 
-        y <= self.boundary.h
+    #[test]
+    fn children_tile_the_parent_exactly() {
+        let mut tree = QuadTree::new(10., 20., 999., 777.);
+        for idx in 0..200 {
+            let offset = idx as f32 * 3.;
+            tree.insert(Rect::new(10. + offset, 20. + offset, 1., 1.), ());
+        }
+
+        fn check(node: &Node) {
+            if let Some(children) = &node.children {
+                let area: f32 = children.iter().map(|child| child.boundary.w * child.boundary.h).sum();
+                assert!((area - node.boundary.w * node.boundary.h).abs() < 1.);
+                for child in children.iter() {
+                    assert!(encloses(&node.boundary, &child.boundary));
+                    assert!(child.depth <= QUADTREE_MAX_DEPTH);
+                    check(child);
+                }
+            }
+        }
+
+        assert!(tree.root.children.is_some());
+        check(&tree.root);
     }
-}
\ No newline at end of file
+}
@@ -1,58 +1,180 @@
-use std::cell::RefCell;
-
 use ggez::Context;
 use ggez::graphics::{self};
 use ggez::input::mouse::{self};
 
 use crate::base::CustomRect;
-use crate::state::{State, AllStates};
+use crate::character::chars::CharacterKind;
+use crate::state::{State, AllStates, Payload, Transition};
+use crate::input::{Action, InputState};
+use crate::consts::DEFAULT_LEVEL;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuRow {
+    Characters,
+    Play,
+    Quit
+}
+
+impl MenuRow {
+    fn up(&self) -> Self {
+        match self {
+            MenuRow::Characters => MenuRow::Quit,
+            MenuRow::Play => MenuRow::Characters,
+            MenuRow::Quit => MenuRow::Play
+        }
+    }
+
+    fn down(&self) -> Self {
+        match self {
+            MenuRow::Characters => MenuRow::Play,
+            MenuRow::Play => MenuRow::Quit,
+            MenuRow::Quit => MenuRow::Characters
+        }
+    }
+}
 
 
 pub struct MenuState {
-    rect: CustomRect
+    rect: CustomRect,
+    quit_rect: CustomRect,
+    character_rects: Vec<(CharacterKind, CustomRect)>,
+    selected: CharacterKind,
+    // Row focused by keyboard / gamepad navigation
+    focus: MenuRow
 }
 
 impl MenuState {
     pub fn new(_ctx: &mut Context) ->  Self {
         let (w, h) = graphics::size(_ctx);
+
+        let character_rects = CharacterKind::ALL.iter().enumerate().map(|(idx, kind)| {
+            (*kind, CustomRect::new(
+                w / 2. - 300. / 2. + idx as f32 * 100.,
+                h / 2. - 60. / 2. - 80.,
+                100.,
+                60.
+            ))
+        }).collect();
+
         Self {
             rect: CustomRect::new(
                 w / 2. - 300. / 2.,
                 h / 2. - 60. / 2.,
                 300.,
                 60.
-            )
+            ),
+            quit_rect: CustomRect::new(
+                w / 2. - 300. / 2.,
+                h / 2. - 60. / 2. + 80.,
+                300.,
+                60.
+            ),
+            character_rects: character_rects,
+            selected: CharacterKind::Punk,
+            focus: MenuRow::Play
+        }
+    }
+
+    fn start(&self) -> Transition {
+        Transition::Switch(AllStates::Play, Payload::StartLevel {
+            character: self.selected,
+            level: DEFAULT_LEVEL.to_string()
+        })
+    }
+
+    fn cycle_character(&mut self, step: i32) {
+        let count = CharacterKind::ALL.len() as i32;
+        let idx = CharacterKind::ALL.iter().position(|kind| *kind == self.selected).unwrap() as i32;
+        self.selected = CharacterKind::ALL[(idx + step).rem_euclid(count) as usize];
+    }
+
+    fn label(&self, row: MenuRow, text: &str) -> String {
+        if self.focus == row {
+            format!("> {}", text)
+        } else {
+            text.to_string()
         }
     }
 }
 
 
+fn draw_button(ctx: &mut Context, rect: &mut CustomRect, label: &str, color: graphics::Color) {
+    rect.draw(ctx);
+    let draw_params = graphics::DrawParam::new()
+        .dest(glam::Vec2::new(rect.fields.x, rect.fields.y));
+
+    let text = graphics::Text::new(label);
+    graphics::draw(ctx, &text, draw_params.color(color)).unwrap();
+}
+
+
 impl State for MenuState {
 
-    fn enter(&self, _ctx: &mut Context, _current_state: &RefCell<AllStates>) {}
+    fn enter(&mut self, _ctx: &mut Context, _payload: &Payload) {}
 
-    fn exit(&self, _ctx: &mut Context, _current_state: &RefCell<AllStates>) {}
+    fn exit(&mut self, _ctx: &mut Context) {}
 
-    fn draw(&mut self, ctx: &mut Context, _current_state: &RefCell<AllStates>) {
-        self.rect.draw(ctx);
-        let draw_params = graphics::DrawParam::new()
-            .dest(glam::Vec2::new(self.rect.fields.x, self.rect.fields.y));
+    fn draw(&mut self, ctx: &mut Context, _alpha: f32) {
+        let play_label = self.label(MenuRow::Play, "Play");
+        let quit_label = self.label(MenuRow::Quit, "Quit");
+        draw_button(ctx, &mut self.rect, &play_label, graphics::Color::RED);
+        draw_button(ctx, &mut self.quit_rect, &quit_label, graphics::Color::RED);
 
-        let text = graphics::Text::new("Play");
-        graphics::draw(ctx, &text, draw_params.color(graphics::Color::RED)).unwrap();
+        let focused = self.focus == MenuRow::Characters;
+        for (kind, rect) in self.character_rects.iter_mut() {
+            let color = if *kind == self.selected { graphics::Color::GREEN } else { graphics::Color::BLACK };
+            let label = if focused && *kind == self.selected {
+                format!("> {}", kind.name())
+            } else {
+                kind.name().to_string()
+            };
+            draw_button(ctx, rect, &label, color);
+        }
     }
 
-    fn update(&mut self, _ctx: &mut Context, current_state: &RefCell<AllStates>) {
+    fn update(&mut self, _ctx: &mut Context, _dt: f32, input: &InputState) -> Transition {
+        if input.is_just_pressed(Action::MenuUp) {
+            self.focus = self.focus.up();
+        } else if input.is_just_pressed(Action::MenuDown) {
+            self.focus = self.focus.down();
+        }
+
+        if self.focus == MenuRow::Characters {
+            if input.is_just_pressed(Action::MoveLeft) {
+                self.cycle_character(-1);
+            } else if input.is_just_pressed(Action::MoveRight) {
+                self.cycle_character(1);
+            }
+        }
+
+        if input.is_just_pressed(Action::Confirm) {
+            match self.focus {
+                MenuRow::Characters => self.focus = MenuRow::Play,
+                MenuRow::Play => return self.start(),
+                MenuRow::Quit => return Transition::Quit
+            }
+        }
+
+        if !input.is_clicked(mouse::MouseButton::Left) {
+            return Transition::None;
+        }
+
         let point = mouse::position(_ctx);
-        let (mouse_x, mouse_y) = (point.x, point.y);
-        if mouse_x > self.rect.fields.x && 
-           mouse_x < self.rect.fields.x + self.rect.fields.w &&
-           mouse_y > self.rect.fields.y && 
-           mouse_y < self.rect.fields.y + self.rect.fields.h
-        {
-            if mouse::button_pressed(_ctx, mouse::MouseButton::Left) {
-                current_state.replace(AllStates::Play);
+        for (kind, rect) in &self.character_rects {
+            if rect.fields.contains(point) {
+                self.selected = *kind;
             }
         }
+
+        if self.rect.fields.contains(point) {
+            return self.start();
+        }
+
+        if self.quit_rect.fields.contains(point) {
+            return Transition::Quit;
+        }
+
+        Transition::None
     }
 }
\ No newline at end of file
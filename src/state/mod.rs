@@ -1,8 +1,10 @@
 use std::collections::HashMap;
-use std::cell::RefCell;
 
 use ggez::Context;
 
+use crate::character::chars::CharacterKind;
+use crate::input::InputState;
+
 
 pub mod menu;
 pub use menu::MenuState;
@@ -10,49 +12,150 @@ pub use menu::MenuState;
 pub mod play;
 pub use play::PlayState;
 
+pub mod pause;
+pub use pause::PauseState;
+
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AllStates {
     Menu,
-    Play
+    Play,
+    Pause
+}
+
+
+/// Data handed to a state when it is entered.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Payload {
+    Empty,
+    StartLevel {
+        character: CharacterKind,
+        level: String
+    }
+}
+
+
+/// Change of the state stack requested by `State::update`.
+/// It is applied by the `StateMachine` once the update finishes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transition {
+    None,
+    Push(AllStates, Payload),
+    Pop,
+    Switch(AllStates, Payload),
+    Quit
 }
 
 
 pub trait State {
-    fn enter(&self, _ctx: &mut Context, _current_state: &RefCell<AllStates>);
-    fn exit(&self, _ctx: &mut Context, _current_state: &RefCell<AllStates>);
-    fn draw(&mut self, _ctx: &mut Context, _current_state: &RefCell<AllStates>);
-    fn update(&mut self, _ctx: &mut Context, _current_state: &RefCell<AllStates>);
+    fn enter(&mut self, _ctx: &mut Context, _payload: &Payload);
+    fn exit(&mut self, _ctx: &mut Context);
+    fn draw(&mut self, _ctx: &mut Context, _alpha: f32);
+    fn update(&mut self, _ctx: &mut Context, _dt: f32, _input: &InputState) -> Transition;
+
+    // Another state was pushed on top of this one
+    fn pause(&mut self, _ctx: &mut Context) {}
+
+    // The state on top of this one was popped
+    fn resume(&mut self, _ctx: &mut Context) {}
+
+    // Overlays let the state underneath keep drawing (frozen) behind them
+    fn is_overlay(&self) -> bool {
+        false
+    }
 }
 
 
 pub type StateFunctions = HashMap<AllStates, Box<dyn State>>;
 
 
+/// Stack of active states. Only the top state is updated, states below an
+/// overlay are still drawn. Every state kind lives on the stack at most once.
 pub struct StateMachine {
-    states: RefCell<StateFunctions>,
-    current: RefCell<AllStates>
+    states: StateFunctions,
+    stack: Vec<AllStates>
 }
 
 
 impl StateMachine {
 
-    pub fn new(states: StateFunctions, current: AllStates) -> Self {
-        Self {
-            states: RefCell::new(states),
-            current: RefCell::new(current)
+    pub fn new(_ctx: &mut Context, states: StateFunctions, current: AllStates, payload: Payload) -> Self {
+        let mut state_machine = Self {
+            states: states,
+            stack: Vec::new()
+        };
+        state_machine.push(_ctx, current, &payload);
+        state_machine
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    pub fn push(&mut self, _ctx: &mut Context, next: AllStates, payload: &Payload) {
+        if let Some(top) = self.stack.last() {
+            self.states.get_mut(top).unwrap().pause(_ctx);
+        }
+
+        self.stack.push(next);
+        self.states.get_mut(&next).unwrap().enter(_ctx, payload);
+    }
+
+    pub fn pop(&mut self, _ctx: &mut Context) {
+        if let Some(top) = self.stack.pop() {
+            self.states.get_mut(&top).unwrap().exit(_ctx);
+        }
+
+        if let Some(top) = self.stack.last() {
+            self.states.get_mut(top).unwrap().resume(_ctx);
+        }
+    }
+
+    pub fn switch(&mut self, _ctx: &mut Context, next: AllStates, payload: &Payload) {
+        if let Some(top) = self.stack.pop() {
+            self.states.get_mut(&top).unwrap().exit(_ctx);
+        }
+
+        self.stack.push(next);
+        self.states.get_mut(&next).unwrap().enter(_ctx, payload);
+    }
+
+    pub fn clear(&mut self, _ctx: &mut Context) {
+        while let Some(top) = self.stack.pop() {
+            self.states.get_mut(&top).unwrap().exit(_ctx);
         }
     }
 
-    pub fn update(&mut self, _ctx: &mut Context) {
-        let mut borrowed = self.states.borrow_mut();
-        let current = borrowed.get_mut(&*self.current.borrow()).unwrap();
-        current.update(_ctx, &self.current);
+    pub fn update(&mut self, _ctx: &mut Context, dt: f32, input: &InputState) {
+        let transition = match self.stack.last() {
+            Some(top) => self.states.get_mut(top).unwrap().update(_ctx, dt, input),
+            None => Transition::None
+        };
+
+        match transition {
+            Transition::None => (),
+            Transition::Push(next, payload) => self.push(_ctx, next, &payload),
+            Transition::Pop => self.pop(_ctx),
+            Transition::Switch(next, payload) => self.switch(_ctx, next, &payload),
+            Transition::Quit => self.clear(_ctx)
+        }
     }
 
-    pub fn draw(&mut self, _ctx: &mut Context) {
-        let mut borrowed = self.states.borrow_mut();
-        let current = borrowed.get_mut(&*self.current.borrow()).unwrap();
-        current.draw(_ctx, &self.current)
+    pub fn draw(&mut self, _ctx: &mut Context, alpha: f32) {
+        if self.stack.is_empty() {
+            return;
+        }
+
+        let mut bottom = self.stack.len() - 1;
+        while bottom > 0 && self.states.get(&self.stack[bottom]).unwrap().is_overlay() {
+            bottom -= 1;
+        }
+
+        let top = self.stack.len() - 1;
+        for (idx, state) in self.stack.iter().enumerate().skip(bottom) {
+            // Paused states are not ticking, so there is nothing to interpolate
+            let alpha = if idx == top { alpha } else { 1. };
+            self.states.get_mut(state).unwrap().draw(_ctx, alpha);
+        }
     }
 }
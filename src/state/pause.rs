@@ -0,0 +1,68 @@
+use ggez::Context;
+use ggez::graphics::{self};
+use ggez::input::mouse::{self};
+
+use crate::base::CustomRect;
+use crate::state::{State, Payload, Transition};
+use crate::input::{Action, InputState};
+
+
+pub struct PauseState {
+    rect: CustomRect
+}
+
+impl PauseState {
+    pub fn new(_ctx: &mut Context) -> Self {
+        let (w, h) = graphics::size(_ctx);
+        Self {
+            rect: CustomRect::new(
+                w / 2. - 300. / 2.,
+                h / 2. - 60. / 2.,
+                300.,
+                60.
+            )
+        }
+    }
+}
+
+
+impl State for PauseState {
+
+    fn enter(&mut self, _ctx: &mut Context, _payload: &Payload) {}
+
+    fn exit(&mut self, _ctx: &mut Context) {}
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _alpha: f32) {
+        let screen = graphics::screen_coordinates(ctx);
+        let shade = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            screen,
+            graphics::Color::new(0., 0., 0., 0.5)
+        ).unwrap();
+        graphics::draw(ctx, &shade, graphics::DrawParam::default()).unwrap();
+
+        self.rect.draw(ctx);
+        let draw_params = graphics::DrawParam::new()
+            .dest(glam::Vec2::new(self.rect.fields.x, self.rect.fields.y));
+
+        let text = graphics::Text::new("Resume");
+        graphics::draw(ctx, &text, draw_params.color(graphics::Color::RED)).unwrap();
+    }
+
+    fn update(&mut self, _ctx: &mut Context, _dt: f32, input: &InputState) -> Transition {
+        let close = input.is_just_pressed(Action::Pause) || input.is_just_pressed(Action::Confirm);
+        let clicked = input.is_clicked(mouse::MouseButton::Left) &&
+            self.rect.fields.contains(mouse::position(_ctx));
+
+        if close || clicked {
+            return Transition::Pop;
+        }
+
+        Transition::None
+    }
+}
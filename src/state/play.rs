@@ -1,66 +1,344 @@
-use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 
 use ggez::Context;
+use ggez::graphics::{self, Rect};
 
-use crate::state::{State, AllStates};
+use glam::Vec2;
+
+use crate::state::{State, AllStates, Payload, Transition};
 use crate::character::{Character};
 use crate::base::{LocationType, ObjectLocation};
-use crate::quadtree::QuadTree;
-use crate::character::chars::{Punk, Biker, Cyborg};
-use crate::tile::{Background, ParkBackground, Floor};
+use crate::collisions::{ColliderId, CollisionWorld, Layer};
+use crate::level::{LevelConfig, TileMap, TriggerSpawn};
+use crate::camera::Camera;
+use crate::character::chars::CharacterKind;
+use crate::tile::{AnimatedObject, Background, ParkBackground, Floor, Interaction, Prop, PropLayer};
+use crate::animation::AnimationEvent;
+use crate::consts::{HIT_TRAUMA, LANDING_SHAKE_SPEED, LANDING_TRAUMA};
+use crate::input::{Action, InputConfig, InputState};
 
 
-pub struct PlayState {
+// Level geometry, a tile map when the level has one
+enum Terrain {
+    Floor(Floor),
+    Map(TileMap)
+}
+
+
+// Trigger area from the level file, fires as the player walks in
+struct Trigger {
+    spawn: TriggerSpawn,
+    inside: bool
+}
+
+
+// Level being played, built as the play state is entered
+struct Level {
     player: Character,
+    // Stand around until they get some behaviour of their own
+    enemies: Vec<Character>,
+    // Input that never presses anything, drives the enemies
+    idle: InputState,
     background: Background,
-    floor: Floor
+    terrain: Terrain,
+    props: Vec<Prop>,
+    objects: Vec<AnimatedObject>,
+    world: CollisionWorld,
+    camera: Camera,
+    triggers: HashMap<ColliderId, Trigger>,
+    // Objects collected so far by kind
+    inventory: BTreeMap<String, u32>,
+    name: String
 }
 
-fn update_quadtree(tree: &mut QuadTree, location: LocationType) {
-    match location {
-        LocationType::Single(rect) => {
-            tree.insert(rect.x, rect.y, rect);
-        },
-        LocationType::Multiple(locations) => {
-            for loc in locations {
-                tree.insert(loc.x, loc.y, loc);
-            }
-        }
+fn add_terrain(world: &mut CollisionWorld, location: LocationType) {
+    let locations = match location {
+        LocationType::Single(rect) => vec![rect],
+        LocationType::Multiple(locations) => locations
+    };
+
+    for loc in locations {
+        world.add(loc, Layer::TERRAIN, Layer::TERRAIN.default_mask());
     }
 }
 
-impl PlayState {
-    pub fn new(ctx: &mut Context) ->  Self {
+impl Level {
+    /// Builds `level` from its level file, `character` replaces the level's player character.
+    fn start(ctx: &mut Context, character: Option<CharacterKind>, level: &str) -> Self {
+
+        let config = LevelConfig::new(ctx, level);
+        let map = match config.terrain(ctx) {
+            Ok(map) => map,
+            Err(e) => {
+                eprintln!("Could not load the geometry of level {}, using a flat floor: {}", level, e);
+                None
+            }
+        };
+
+        let bounds = config.bounds(ctx, map.as_ref().map(|map| map.bounds()));
+        let theme = config.background.or_else(|| map.as_ref().and_then(|map| map.background())).unwrap_or_default();
+        let mut trigger_spawns = config.triggers.clone();
+        if let Some(map) = &map {
+            trigger_spawns.extend(map.triggers());
+        }
+        let mut world = CollisionWorld::new(config.broad_phase, bounds);
+
+        let terrain = match map {
+            Some(map) => {
+                for (rect, layer) in map.colliders() {
+                    world.add(rect, layer, layer.default_mask());
+                }
+                Terrain::Map(map)
+            },
+            None => {
+                let floor = Floor::new(ctx, bounds);
+                add_terrain(&mut world, floor.get_location(ctx));
+                Terrain::Floor(floor)
+            }
+        };
+
+        let mut props = Vec::new();
+        for spawn in &config.props {
+            match spawn.build(ctx) {
+                Ok(mut prop) => {
+                    prop.register(&mut world);
+                    props.push(prop);
+                },
+                Err(e) => eprintln!("Could not load prop {}: {}", spawn.image, e)
+            }
+        }
 
-        let mut player = Punk::new(ctx);
-        let floor = Floor::new(ctx);
+        let mut player = character.unwrap_or(config.player.character).build(ctx);
+        player.spawn_at(config.player.x, config.player.y);
+        player.register(&mut world, Layer::PLAYER);
 
-        update_quadtree(&mut player.quadtree, floor.get_location(ctx));
+        let enemies = config.enemies.iter().map(|spawn| {
+            let mut enemy = spawn.character.build(ctx);
+            enemy.spawn_at(spawn.x, spawn.y);
+            enemy.register(&mut world, Layer::ENEMY);
+            enemy
+        }).collect();
+
+        let objects = config.objects.iter().map(|spawn| {
+            let mut object = spawn.build(ctx);
+            object.register(&mut world);
+            object
+        }).collect();
+
+        let triggers = trigger_spawns.into_iter().map(|spawn| {
+            let id = world.add(spawn.bounds(), Layer::TRIGGER, Layer::TRIGGER.default_mask());
+            (id, Trigger {
+                spawn: spawn,
+                inside: false
+            })
+        }).collect();
+
+        let mut camera = Camera::new(ctx, config.camera, bounds);
+        camera.snap_to(player.hitbox());
 
         Self {
             player: player,
-            background: ParkBackground::new(ctx),
-            floor: floor
+            enemies: enemies,
+            idle: InputState::with_config(InputConfig::default()),
+            background: ParkBackground::new(ctx, theme),
+            terrain: terrain,
+            props: props,
+            objects: objects,
+            world: world,
+            camera: camera,
+            triggers: triggers,
+            inventory: BTreeMap::new(),
+            name: level.to_string()
         }
     }
+
+    // Lets the player interact with the objects it touches and fires the triggers it walked into
+    fn touch_objects(&mut self) {
+        let touching = match self.player.collider {
+            Some(id) => self.world.overlapping(id),
+            None => Vec::new()
+        };
+
+        let mut collected = Vec::new();
+        for (idx, object) in self.objects.iter_mut().enumerate() {
+            if !object.collider.map_or(false, |id| touching.contains(&id)) {
+                continue;
+            }
+
+            // Opening only plays the animation, collecting also takes the object out of the level
+            if object.interact() == Some(Interaction::Collect) {
+                collected.push(idx);
+            }
+        }
+
+        for idx in collected.into_iter().rev() {
+            let object = self.objects.remove(idx);
+            if let Some(id) = object.collider {
+                self.world.remove(id);
+            }
+            *self.inventory.entry(object.kind.name().to_string()).or_insert(0) += 1;
+        }
+
+        let mut spent = Vec::new();
+        for (id, trigger) in self.triggers.iter_mut() {
+            let inside = touching.contains(id);
+            if inside && !trigger.inside {
+                if let Some(pan) = trigger.spawn.pan {
+                    self.camera.pan(pan.target(), pan.duration, pan.hold);
+                }
+                if let Some(zoom) = trigger.spawn.zoom {
+                    self.camera.zoom_to(zoom);
+                }
+            } else if !inside && trigger.inside {
+                if trigger.spawn.zoom.is_some() {
+                    let zoom = self.camera.params.zoom;
+                    self.camera.zoom_to(zoom);
+                }
+                // Once triggers stay until the player is out, so their zoom gets undone
+                if trigger.spawn.once {
+                    spent.push(*id);
+                }
+            }
+            trigger.inside = inside;
+        }
+
+        for id in spent {
+            self.triggers.remove(&id);
+            self.world.remove(id);
+        }
+    }
+
+    // Reacts to what the player's animation fired this tick
+    fn handle_events(&mut self) {
+        for event in self.player.events() {
+            match event {
+                AnimationEvent::HitStart => {
+                    let attack = match self.player.attack_box() {
+                        Some(attack) => attack,
+                        None => continue
+                    };
+                    if !self.world.query(&attack, Layer::ENEMY, self.player.collider).is_empty() {
+                        self.camera.add_trauma(HIT_TRAUMA);
+                    }
+                },
+                // Nothing listens for these yet
+                AnimationEvent::HitEnd | AnimationEvent::Footstep | AnimationEvent::SpawnProjectile | AnimationEvent::Custom(_) => {}
+            }
+        }
+    }
+
+    fn draw_props(&self, _ctx: &mut Context, layer: PropLayer, view: Rect) {
+        for prop in self.props.iter().filter(|prop| prop.layer == layer && prop.bounds().overlaps(&view)) {
+            prop.draw(_ctx);
+        }
+    }
+
+    fn draw_objects(&self, _ctx: &mut Context, view: Rect) {
+        for object in self.objects.iter().filter(|object| object.bounds().overlaps(&view)) {
+            object.draw(_ctx);
+        }
+    }
+
+    fn draw_inventory(&self, _ctx: &mut Context) {
+        let lines: Vec<String> = self.inventory.iter()
+            .map(|(kind, count)| format!("{}: {}", kind, count))
+            .collect();
+        if lines.is_empty() {
+            return;
+        }
+
+        let text = graphics::Text::new(lines.join("\n"));
+        let params = graphics::DrawParam::new()
+            .dest(Vec2::new(10., 10.))
+            .color(graphics::Color::WHITE);
+        graphics::draw(_ctx, &text, params).unwrap();
+    }
+
+    fn draw(&mut self, _ctx: &mut Context, alpha: f32) {
+        self.background.draw(_ctx, self.camera.position(alpha));
+
+        let view = self.camera.view_rect(alpha);
+        self.camera.apply(_ctx, alpha).unwrap();
+        match &self.terrain {
+            Terrain::Floor(floor) => floor.draw(_ctx),
+            Terrain::Map(map) => map.draw(_ctx, view)
+        }
+        self.draw_props(_ctx, PropLayer::Back, view);
+        self.draw_objects(_ctx, view);
+        self.world.draw_debug(_ctx);
+        for enemy in self.enemies.iter_mut() {
+            enemy.draw(_ctx, alpha);
+        }
+        self.player.draw(_ctx, alpha);
+        self.draw_props(_ctx, PropLayer::Front, view);
+        self.camera.reset(_ctx).unwrap();
+
+        self.draw_inventory(_ctx);
+    }
+
+    fn update(&mut self, _ctx: &mut Context, dt: f32, input: &InputState) {
+        let falling = self.player.body.velocity.y;
+        self.player.update(_ctx, dt, input, &mut self.world).unwrap();
+        for enemy in self.enemies.iter_mut() {
+            enemy.update(_ctx, dt, &self.idle, &mut self.world).unwrap();
+        }
+        for object in self.objects.iter_mut() {
+            object.update(dt);
+        }
+        self.touch_objects();
+        self.handle_events();
+
+        if self.player.body.on_ground && falling > LANDING_SHAKE_SPEED {
+            let impact = falling / self.player.body.params.terminal_velocity;
+            self.camera.add_trauma(LANDING_TRAUMA * impact);
+        }
+        self.camera.follow(_ctx, self.player.hitbox(), self.player.body.velocity, dt);
+    }
+}
+
+
+pub struct PlayState {
+    // Nothing to play until the menu starts a level
+    level: Option<Level>
 }
 
+impl PlayState {
+    pub fn new(_ctx: &mut Context) -> Self {
+        Self {
+            level: None
+        }
+    }
+}
 
 impl State for PlayState {
 
 
-    fn enter(&self, _ctx: &mut Context, _current_state: &RefCell<AllStates>) {}
+    fn enter(&mut self, _ctx: &mut Context, payload: &Payload) {
+        if let Payload::StartLevel { character, level } = payload {
+            self.level = Some(Level::start(_ctx, Some(*character), level));
+        }
+        if let Some(level) = &self.level {
+            graphics::set_window_title(_ctx, &format!("Rust 2d game - {}", level.name));
+        }
+    }
 
-    fn exit(&self, _ctx: &mut Context, _current_state: &RefCell<AllStates>) {}
+    fn exit(&mut self, _ctx: &mut Context) {}
 
-    fn draw(&mut self, _ctx: &mut Context, _current_state: &RefCell<AllStates>) {
-        self.background.draw(_ctx);
-        self.floor.draw(_ctx);
-        self.player.draw(_ctx);
+    fn draw(&mut self, _ctx: &mut Context, alpha: f32) {
+        if let Some(level) = &mut self.level {
+            level.draw(_ctx, alpha);
+        }
     }
 
-    fn update(&mut self, _ctx: &mut Context, _current_state: &RefCell<AllStates>) {
-        self.background.update(_ctx).unwrap();
-        self.player.update(_ctx).unwrap();
+    fn update(&mut self, _ctx: &mut Context, dt: f32, input: &InputState) -> Transition {
+        if let Some(level) = &mut self.level {
+            level.update(_ctx, dt, input);
+        }
+
+        if input.is_just_pressed(Action::Pause) {
+            return Transition::Push(AllStates::Pause, Payload::Empty);
+        }
+
+        Transition::None
     }
-}
\ No newline at end of file
+}
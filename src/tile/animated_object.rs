@@ -0,0 +1,158 @@
+use ggez::Context;
+use ggez::graphics::{self, Drawable, Rect};
+
+use glam::Vec2;
+use serde::Deserialize;
+
+use crate::animation::{LoopMode, SpriteAnimation};
+use crate::collisions::{ColliderId, CollisionWorld, Layer};
+use crate::utils::join_paths;
+use crate::consts::PARK_ANIMATED_OBJECTS_DIR;
+
+
+/// Sprite strips in `PARK_ANIMATED_OBJECTS_DIR`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectKind {
+    Fountain,
+    Chest,
+    Money,
+    Card,
+    Skateboard
+}
+
+impl ObjectKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ObjectKind::Fountain => "fountain",
+            ObjectKind::Chest => "chest",
+            ObjectKind::Money => "money",
+            ObjectKind::Card => "card",
+            ObjectKind::Skateboard => "skateboard"
+        }
+    }
+
+    fn image(&self) -> &'static str {
+        match self {
+            ObjectKind::Fountain => "Fountain.png",
+            ObjectKind::Chest => "Chest_open.png",
+            ObjectKind::Money => "Money.png",
+            ObjectKind::Card => "Card.png",
+            ObjectKind::Skateboard => "Skateboard.png"
+        }
+    }
+
+    pub fn playback(&self) -> LoopMode {
+        match self {
+            // Stays open
+            ObjectKind::Chest => LoopMode::Hold,
+            _ => LoopMode::Loop
+        }
+    }
+
+    pub fn interaction(&self) -> Interaction {
+        match self {
+            ObjectKind::Chest => Interaction::Open,
+            ObjectKind::Money | ObjectKind::Card | ObjectKind::Skateboard => Interaction::Collect,
+            ObjectKind::Fountain => Interaction::None
+        }
+    }
+}
+
+
+/// What happens when the player touches an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interaction {
+    None,
+    // The object is picked up and disappears
+    Collect,
+    // The object plays its animation, only the first time
+    Open
+}
+
+
+/// Animated thing placed in a level, e.g. a fountain or a coin.
+pub struct AnimatedObject {
+    pub kind: ObjectKind,
+    animation: SpriteAnimation,
+    bounds: Rect,
+    pub interaction: Interaction,
+    // Looping objects play all the time, the others rest on their first frame until started
+    playing: bool,
+    used: bool,
+    pub collider: Option<ColliderId>
+}
+
+impl AnimatedObject {
+    /// Loads `kind` scaled by `scale`, with its bottom left corner at `position`.
+    pub fn new(_ctx: &mut Context, kind: ObjectKind, position: Vec2, scale: f32, playback: LoopMode, interaction: Interaction) -> Self {
+        let path = join_paths(PARK_ANIMATED_OBJECTS_DIR, kind.image());
+        let mut animation = SpriteAnimation::new(_ctx, &path);
+        animation.timer.mode = playback;
+
+        let size = animation.frame().size * scale;
+
+        Self {
+            kind: kind,
+            animation: animation,
+            bounds: Rect::new(position.x, position.y - size.y, size.x, size.y),
+            interaction: interaction,
+            playing: playback == LoopMode::Loop || playback == LoopMode::PingPong,
+            used: false,
+            collider: None
+        }
+    }
+
+    pub fn bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    /// Adds the object to `world` if the player can interact with it.
+    pub fn register(&mut self, world: &mut CollisionWorld) {
+        if self.interaction != Interaction::None {
+            self.collider = Some(world.add(self.bounds, Layer::PICKUP, Layer::PICKUP.default_mask()));
+        }
+    }
+
+    /// Starts a one shot animation over from its first frame.
+    pub fn play(&mut self) {
+        self.animation.restart();
+        self.playing = true;
+    }
+
+    /// Called when the player touches the object, returns what happened if anything did.
+    pub fn interact(&mut self) -> Option<Interaction> {
+        if self.used {
+            return None;
+        }
+
+        match self.interaction {
+            Interaction::None => return None,
+            Interaction::Collect => {},
+            Interaction::Open => self.play()
+        }
+
+        self.used = true;
+        Some(self.interaction)
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        if self.playing {
+            self.animation.update(dt);
+            // Nothing listens to object events, don't let them pile up
+            self.animation.take_events();
+            self.playing = !self.animation.timer.is_finished();
+        }
+    }
+
+    pub fn draw(&self, _ctx: &mut Context) {
+        let frame = self.animation.frame();
+        let scale = self.bounds.h / frame.size.y;
+        let params = graphics::DrawParam::default()
+            .src(self.animation.src())
+            .dest(Vec2::new(self.bounds.x, self.bounds.y) + frame.offset * scale)
+            .scale(Vec2::new(scale, scale));
+        self.animation.image.draw(_ctx, params).unwrap();
+    }
+}
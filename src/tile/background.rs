@@ -1,49 +1,100 @@
-use std::cell::RefCell;
 use std::collections::BTreeMap;
 
 use glam::Vec2;
 use ggez::graphics::{self, *};
-use ggez::input::keyboard;
-use ggez::event::KeyCode;
-use ggez::{Context, GameResult};
+use ggez::Context;
+
+use serde::Deserialize;
 
 use crate::utils::join_paths;
-use crate::animation::MovingBackground;
-use crate::consts::PARK_DAY_BACKGROUND_DIR;
+use crate::consts::{PARK_DAY_BACKGROUND_DIR, PARK_NIGHT_BACKGROUND_DIR};
 
 
-pub struct Background {
-    static_background: BTreeMap<String, RefCell<graphics::Image>>,
-    moving_background: BTreeMap<String, RefCell<MovingBackground>>
+/// Time of day a level is set in, picks the background images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackgroundTheme {
+    Day,
+    Night
 }
 
+impl Default for BackgroundTheme {
+    fn default() -> Self {
+        BackgroundTheme::Day
+    }
+}
 
-impl Background {
-    pub fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
-        if keyboard::is_key_pressed(_ctx, KeyCode::D) {
-            for (_, value) in &self.moving_background {
-                value.borrow_mut().update(_ctx).unwrap();
-            }
+impl BackgroundTheme {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "day" => Some(BackgroundTheme::Day),
+            "night" => Some(BackgroundTheme::Night),
+            _ => None
         }
+    }
 
-        Ok(())
+    pub fn dir(&self) -> &'static str {
+        match self {
+            BackgroundTheme::Day => PARK_DAY_BACKGROUND_DIR,
+            BackgroundTheme::Night => PARK_NIGHT_BACKGROUND_DIR
+        }
     }
+}
+
+
+/// Background image scrolling at a fraction of the camera movement,
+/// repeated in both directions so it covers levels of any size.
+pub struct ParallaxLayer {
+    image: graphics::Image,
+    // 0 stays fixed on screen, 1 moves with the world
+    factor: Vec2
+}
 
-    pub fn draw(&mut self, _ctx: &mut Context)  {
-
-        let (w, h) = graphics::size(_ctx);
-        for (_, value) in &self.static_background {
-            let image = value.borrow_mut();
-            let scale_x = w / image.width() as f32;
-            let scale_y = h / image.height() as f32;
-            let params = graphics::DrawParam::default()
-                .scale(Vec2::new(scale_x, scale_y))
-                .dest(Vec2::new(0., 0.));
-            image.draw(_ctx, params).unwrap();
+impl ParallaxLayer {
+    pub fn new(_ctx: &mut Context, image_path: &str, factor: Vec2) -> Self {
+        Self {
+            image: graphics::Image::new(_ctx, image_path).unwrap(),
+            factor: factor
         }
+    }
+
+    pub fn draw(&self, _ctx: &mut Context, camera: Vec2) {
+        let screen = graphics::screen_coordinates(_ctx);
+
+        // Fit the height of the screen, the width follows the image's aspect ratio
+        let scale = screen.h / self.image.height() as f32;
+        let tile = Vec2::new(self.image.width() as f32 * scale, screen.h);
 
-        for (_, value) in &self.moving_background {
-            value.borrow_mut().draw(_ctx);
+        let offset = camera * self.factor;
+        let start = Vec2::new(-offset.x.rem_euclid(tile.x), -offset.y.rem_euclid(tile.y));
+
+        let mut y = start.y;
+        while y < screen.h {
+            let mut x = start.x;
+            while x < screen.w {
+                let params = graphics::DrawParam::default()
+                    .scale(Vec2::new(scale, scale))
+                    .dest(Vec2::new(screen.x + x, screen.y + y));
+                self.image.draw(_ctx, params).unwrap();
+                x += tile.x;
+            }
+            y += tile.y;
+        }
+    }
+}
+
+
+pub struct Background {
+    // Drawn back to front in key order
+    layers: BTreeMap<String, ParallaxLayer>
+}
+
+
+impl Background {
+    /// Draws every layer for a camera whose view starts at `camera` in world space.
+    pub fn draw(&mut self, _ctx: &mut Context, camera: Vec2)  {
+        for (_, layer) in &self.layers {
+            layer.draw(_ctx, camera);
         }
     }
 }
@@ -52,19 +103,17 @@ impl Background {
 pub struct ParkBackground;
 
 impl ParkBackground {
-    pub fn new(_ctx: &mut Context) -> Background {
-        let mut static_background = BTreeMap::new();
-        static_background.insert("background1".to_string(), RefCell::new(graphics::Image::new(_ctx, &join_paths(PARK_DAY_BACKGROUND_DIR, "1.png")).unwrap()));
-
-        let mut moving_background = BTreeMap::new();
-        moving_background.insert("background2".to_string(), RefCell::new(MovingBackground::new(_ctx, &join_paths(PARK_DAY_BACKGROUND_DIR, "2.png"), 0.5)));
-        moving_background.insert("background3".to_string(), RefCell::new(MovingBackground::new(_ctx, &join_paths(PARK_DAY_BACKGROUND_DIR, "3.png"), 1.5)));
-        moving_background.insert("background4".to_string(), RefCell::new(MovingBackground::new(_ctx, &join_paths(PARK_DAY_BACKGROUND_DIR, "4.png"), 1.7)));
-        moving_background.insert("background5".to_string(), RefCell::new(MovingBackground::new(_ctx, &join_paths(PARK_DAY_BACKGROUND_DIR, "5.png"), 2.)));
-        
+    pub fn new(_ctx: &mut Context, theme: BackgroundTheme) -> Background {
+        let dir = theme.dir();
+        let mut layers = BTreeMap::new();
+        layers.insert("background1".to_string(), ParallaxLayer::new(_ctx, &join_paths(dir, "1.png"), Vec2::new(0., 0.)));
+        layers.insert("background2".to_string(), ParallaxLayer::new(_ctx, &join_paths(dir, "2.png"), Vec2::new(0.1, 0.05)));
+        layers.insert("background3".to_string(), ParallaxLayer::new(_ctx, &join_paths(dir, "3.png"), Vec2::new(0.25, 0.1)));
+        layers.insert("background4".to_string(), ParallaxLayer::new(_ctx, &join_paths(dir, "4.png"), Vec2::new(0.4, 0.2)));
+        layers.insert("background5".to_string(), ParallaxLayer::new(_ctx, &join_paths(dir, "5.png"), Vec2::new(0.6, 0.3)));
+
         Background {
-            static_background: static_background,
-            moving_background: moving_background
+            layers: layers
         }
     }
-}
\ No newline at end of file
+}
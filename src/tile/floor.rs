@@ -7,6 +7,8 @@ use crate::base::{LocationType, ObjectLocation};
 
 
 pub struct Floor {
+    // Level area the floor spans, it sits at the bottom
+    bounds: Rect,
     left_corner: Image,
     middle: Image,
     right_corner: Image
@@ -21,19 +23,20 @@ impl ObjectLocation for Floor {
 
 
 impl Floor {
-    pub fn new(ctx: &mut Context) -> Self {
+    pub fn new(ctx: &mut Context, bounds: Rect) -> Self {
         Self {
+            bounds: bounds,
             left_corner: Image::new(ctx, "/tiles/park/tiles/Tile_01.png").unwrap(),
             middle: Image::new(ctx, "/tiles/park/tiles/Tile_02.png").unwrap(),
             right_corner: Image::new(ctx, "/tiles/park/tiles/Tile_02.png").unwrap()
         }
     }
 
-    pub fn generate_location(&self, ctx: &mut Context) -> Vec<Rect> {
+    pub fn generate_location(&self, _ctx: &mut Context) -> Vec<Rect> {
         let mut res = Vec::new();
-        let (w, h) = graphics::size(ctx);
+        let (w, h) = (self.bounds.x + self.bounds.w, self.bounds.y + self.bounds.h);
         res.push(Rect {
-            x: 0.,
+            x: self.bounds.x,
             y: h - self.left_corner.height() as f32 * 2.,
             w: self.left_corner.width() as f32,
             h: self.left_corner.height() as f32
@@ -41,9 +44,9 @@ impl Floor {
 
         let left_width = self.left_corner.width();
         let middle_height = self.middle.height();
-        for i in 1..(w as u16 / self.left_corner.width() - 1) {
+        for i in 1..(self.bounds.w as u16 / self.left_corner.width() - 1) {
             res.push(Rect {
-                x: (left_width * i) as f32,
+                x: self.bounds.x + (left_width * i) as f32,
                 y: h - middle_height as f32 * 2.,
                 w: self.middle.width() as f32,
                 h: self.middle.height() as f32
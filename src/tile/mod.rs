@@ -1,5 +1,11 @@
 pub mod background;
-pub use background::{Background, ParkBackground};
+pub use background::{Background, BackgroundTheme, ParkBackground};
 
 pub mod floor;
-pub use floor::Floor;
\ No newline at end of file
+pub use floor::Floor;
+
+pub mod prop;
+pub use prop::{Prop, PropCollision, PropLayer};
+
+pub mod animated_object;
+pub use animated_object::{AnimatedObject, Interaction, ObjectKind};
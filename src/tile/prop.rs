@@ -0,0 +1,92 @@
+use ggez::{Context, GameResult};
+use ggez::graphics::{self, Drawable, Image, Rect};
+
+use glam::Vec2;
+use serde::Deserialize;
+
+use crate::collisions::{ColliderId, CollisionWorld, Layer};
+
+
+/// Side of the characters a prop is drawn on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PropLayer {
+    Back,
+    Front
+}
+
+impl Default for PropLayer {
+    fn default() -> Self {
+        PropLayer::Back
+    }
+}
+
+
+/// How characters collide with a prop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PropCollision {
+    // Scenery only
+    None,
+    // Blocks from every side, like a fence
+    Solid,
+    // Can be stood on and passed through from below or the sides, like a bench
+    Platform
+}
+
+impl Default for PropCollision {
+    fn default() -> Self {
+        PropCollision::None
+    }
+}
+
+
+/// Decoration placed in a level, e.g. a bench or a bush.
+pub struct Prop {
+    image: Image,
+    bounds: Rect,
+    pub layer: PropLayer,
+    pub collision: PropCollision,
+    pub collider: Option<ColliderId>
+}
+
+impl Prop {
+    /// Loads `image_path` scaled by `scale`, with its bottom left corner at `position`.
+    pub fn new(_ctx: &mut Context, image_path: &str, position: Vec2, scale: f32, layer: PropLayer, collision: PropCollision) -> GameResult<Self> {
+        let image = Image::new(_ctx, image_path)?;
+        let (w, h) = (image.width() as f32 * scale, image.height() as f32 * scale);
+
+        Ok(Self {
+            image: image,
+            bounds: Rect::new(position.x, position.y - h, w, h),
+            layer: layer,
+            collision: collision,
+            collider: None
+        })
+    }
+
+    pub fn bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    /// Adds the prop to `world` if characters collide with it.
+    pub fn register(&mut self, world: &mut CollisionWorld) {
+        let layer = match self.collision {
+            PropCollision::None => return,
+            PropCollision::Solid => Layer::TERRAIN,
+            PropCollision::Platform => Layer::PLATFORM
+        };
+
+        self.collider = Some(world.add(self.bounds, layer, layer.default_mask()));
+    }
+
+    pub fn draw(&self, _ctx: &mut Context) {
+        let params = graphics::DrawParam::default()
+            .dest(Vec2::new(self.bounds.x, self.bounds.y))
+            .scale(Vec2::new(
+                self.bounds.w / self.image.width() as f32,
+                self.bounds.h / self.image.height() as f32
+            ));
+        self.image.draw(_ctx, params).unwrap();
+    }
+}
@@ -1,3 +1,37 @@
+#[cfg(test)]
+pub mod test_rng;
+
+
 pub fn join_paths(root: &str, path: &str) -> String {
     format!("{}/{}", root, path)
-}
\ No newline at end of file
+}
+
+/// Resolves `path` relative to the directory `root`, folding `.` and `..` segments.
+/// Absolute paths are returned as they are.
+pub fn resolve_path(root: &str, path: &str) -> String {
+    if path.starts_with('/') {
+        return path.to_string();
+    }
+
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in root.split('/').chain(path.split('/')) {
+        match segment {
+            "" | "." => {},
+            ".." => {
+                segments.pop();
+            },
+            _ => segments.push(segment)
+        }
+    }
+
+    format!("/{}", segments.join("/"))
+}
+
+
+/// Directory part of a resource path, `/` for top level files.
+pub fn parent_dir(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(idx) => &path[..idx]
+    }
+}
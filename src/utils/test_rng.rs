@@ -0,0 +1,29 @@
+use ggez::graphics::Rect;
+
+
+// Small xorshift generator, keeps the tests deterministic without extra crates
+pub struct Rng(pub u64);
+
+impl Rng {
+    /// Uniform in [0, 1).
+    pub fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + self.next() * (max - min)
+    }
+
+    /// Rect up to `size` on each side overlapping `area` or poking out of its top left.
+    pub fn rect(&mut self, area: &Rect, size: f32) -> Rect {
+        Rect::new(
+            self.range(area.x - size, area.x + area.w),
+            self.range(area.y - size, area.y + area.h),
+            self.range(0., size),
+            self.range(0., size)
+        )
+    }
+}